@@ -0,0 +1,47 @@
+#ifndef DBAP_H
+#define DBAP_H
+
+/* Generated by cbindgen from the `capi` module. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of a call into the C API.
+typedef enum DbapStatus {
+  // The call succeeded and the output buffer was written.
+  DBAP_OK = 0,
+  // One of the given pointers was null. The output buffer was not written.
+  DBAP_NULL_POINTER = 1,
+  // The given length was `0`. The output buffer was not written.
+  DBAP_INVALID_LENGTH = 2,
+} DbapStatus;
+
+// Compute the gain of each of `len` speakers from their distances and weights, writing the
+// results to `out`. See `flat_gains_f32`.
+//
+// # Safety
+//
+// Unless null, `distances`, `weights` and `out` must each point to `len` valid, properly
+// aligned `f32`s, and `out` must not overlap `distances` or `weights`.
+enum DbapStatus dbap_compute_gains_f32(const float *distances,
+                                       const float *weights,
+                                       size_t len,
+                                       float rolloff_db,
+                                       float *out);
+
+// The same as `dbap_compute_gains_f32`, but for `f64`s.
+//
+// # Safety
+//
+// Unless null, `distances`, `weights` and `out` must each point to `len` valid, properly
+// aligned `f64`s, and `out` must not overlap `distances` or `weights`.
+enum DbapStatus dbap_compute_gains_f64(const double *distances,
+                                       const double *weights,
+                                       size_t len,
+                                       double rolloff_db,
+                                       double *out);
+
+#endif  /* DBAP_H */
@@ -1,19 +1,66 @@
 //! An implementation of Distance-Based Amplitude Panning as published by Trond Lossius, 2009.
+//!
+//! ## Features
+//!
+//! - `std` (default): links the standard library, implementing `std::error::Error` for the
+//!   crate's error types. Implies `alloc`.
+//! - `alloc`: enables the APIs that allocate, e.g. those returning a `Vec` or `String`. Without
+//!   `alloc` the crate is strictly `no_std`, retaining the core `SpeakerGains` calculation along
+//!   with the helpers that do not allocate.
+//! - `libm`: provides the float operations required by `num-traits` via `libm`, required when
+//!   `full` is enabled without `std`. E.g. `default-features = false, features = ["full",
+//!   "alloc", "libm"]` for an `alloc`-only target.
+//! - `full` (default): `Scalar` is built upon `num-traits`, allowing for any type implementing
+//!   the necessary `num-traits` operations. Also enables the helpers requiring trigonometry.
+//! - `minimal`: drops the `num-traits` dependency in favour of a tiny `Pow` trait implemented for
+//!   `f32` and `f64` only. Implies `std`. Enable via `default-features = false, features =
+//!   ["minimal"]`.
+//! - `approx`: implements the `approx` crate's `AbsDiffEq` and `RelativeEq` for `Speaker`.
+//! - `capi`: exposes a C API within the `capi` module, described by the header `include/dbap.h`.
+//! - `rayon`: parallel batch computation of gains via `rayon`. Implies `std`.
+//! - `random-layouts`: reproducible pseudo-random speaker layouts for demos and tests. Implies
+//!   `full`. Implies `alloc`.
 
-use num_traits::Pow;
-use std::iter::Sum;
-use std::ops::{Add, Div, Mul, Neg, Sub};
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(all(feature = "full", not(feature = "std"), not(feature = "libm")))]
+compile_error!("the `full` feature requires either the `std` or `libm` feature");
+#[cfg(all(not(feature = "full"), not(feature = "std")))]
+compile_error!("the `minimal` `Pow` implementation requires the `std` feature");
+
+#[cfg(feature = "alloc")]
+use alloc::{format, string::String, string::ToString, vec, vec::Vec};
+use core::iter::Sum;
+use core::ops::{Add, Div, Mul, Neg, Sub};
+#[cfg(all(feature = "full", feature = "alloc"))]
+use num_traits::FloatConst;
+#[cfg(feature = "full")]
+use num_traits::{Float, Pow};
 
 /// Scalar values compatible with the DBAP algorithm, used to represent distances, coefficients,
 /// weights, etc.
 ///
 /// The purpose of this trait is to allow the DBAP algorithm to be generic over the types of values
 /// used (e.g. `f32`, `f64`).
+///
+/// Implemented for `f32` and `f64`. Custom types must implement the operator supertraits along
+/// with `from_f32`, which is used to produce all literal values rather than requiring `From<f32>`.
+///
+/// Nothing within the crate assumes a native float width. Every literal produced via `from_f32` is
+/// exactly representable as an `f32` (e.g. small integers and halves, along with counts of up to
+/// `2²⁴`), and all other values are derived via the type's own operators. As a result, a
+/// higher-precision type (e.g. a double-double or arbitrary precision float) retains its full
+/// precision throughout the calculation, provided its `Pow` implementation does.
 pub trait Scalar:
     Sized
     + Copy
-    + From<DefaultScalar>
     + PartialEq
+    + PartialOrd
     + Add<Self, Output = Self>
     + Div<Self, Output = Self>
     + Mul<Self, Output = Self>
@@ -22,28 +69,77 @@ pub trait Scalar:
     + Sub<Self, Output = Self>
     + Sum<Self>
 {
+    /// Convert the given `f32` into a value of this type.
+    ///
+    /// All literal values used throughout the DBAP calculation (e.g. `0.0`, `2.0`, `20.0`) are
+    /// produced via this constructor.
+    fn from_f32(f: f32) -> Self;
+
+    /// Whether or not the value is neither infinite nor NaN.
+    ///
+    /// The default implementation relies on `self - self` being `0` for all finite values and NaN
+    /// otherwise.
+    #[allow(clippy::eq_op)]
+    fn is_finite(self) -> bool {
+        let zero = self - self;
+        zero == zero
+    }
+
+    /// Whether or not the value is NaN.
+    ///
+    /// The default implementation relies on NaN being the only value not equal to itself.
+    #[allow(clippy::eq_op)]
+    fn is_nan(self) -> bool {
+        self != self
+    }
 }
 
-impl<T> Scalar for T where
-    T: Sized
-        + Copy
-        + From<DefaultScalar>
-        + PartialEq
-        + Add<Self, Output = Self>
-        + Div<Self, Output = Self>
-        + Mul<Self, Output = Self>
-        + Neg<Output = Self>
-        + Pow<Self, Output = Self>
-        + Sub<Self, Output = Self>
-        + Sum<Self>
-{
+impl Scalar for f32 {
+    fn from_f32(f: f32) -> Self {
+        f
+    }
+}
+
+impl Scalar for f64 {
+    fn from_f32(f: f32) -> Self {
+        f.into()
+    }
+}
+
+/// A minimal stand-in for `num_traits::Pow`, used in place of `num-traits` when the `full` feature
+/// is disabled.
+#[cfg(not(feature = "full"))]
+pub trait Pow<RHS> {
+    /// The result of raising `self` to the power of `RHS`.
+    type Output;
+    /// Raise `self` to the power of `rhs`.
+    fn pow(self, rhs: RHS) -> Self::Output;
+}
+
+#[cfg(not(feature = "full"))]
+impl Pow<f32> for f32 {
+    type Output = f32;
+    fn pow(self, rhs: f32) -> f32 {
+        self.powf(rhs)
+    }
+}
+
+#[cfg(not(feature = "full"))]
+impl Pow<f64> for f64 {
+    type Output = f64;
+    fn pow(self, rhs: f64) -> f64 {
+        self.powf(rhs)
+    }
 }
 
 /// The default scalar type used to represent the space.
 pub type DefaultScalar = f32;
 
 /// A speaker within the DBAP space calculation.
-#[derive(Copy, Clone, Debug)]
+///
+/// `Eq` and `Hash` are only implemented where `S` implements them (e.g. integer or fixed-point
+/// scalars), allowing layouts to be used as keys for memoisation without hashing floats.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Speaker<S = DefaultScalar> {
     /// The speaker's distance from the virtual location.
     pub distance: S,
@@ -51,157 +147,6483 @@ pub struct Speaker<S = DefaultScalar> {
     pub weight: S,
 }
 
-/// An iterator yielding the gain for each given speaker, given their weights and distance from the
-/// source position.
-#[derive(Clone)]
-pub struct SpeakerGains<'a, S = DefaultScalar> {
-    speakers: &'a [Speaker<S>],
-    a_coefficient: S,
-    k_coefficient: S,
-    i: usize,
+impl<S> Speaker<S>
+where
+    S: Scalar,
+{
+    /// A speaker at the given distance with the given weight.
+    pub fn new(distance: S, weight: S) -> Self {
+        Speaker { distance, weight }
+    }
 }
 
-impl<'a, S> SpeakerGains<'a, S>
+/// A speaker at a distance of `0.0` with a weight of `1.0`.
+impl<S> Default for Speaker<S>
 where
     S: Scalar,
 {
-    /// Given:
-    ///
-    /// - a list of speaker distances from the virtual source:
-    /// - weights for each of those speakers and
-    /// - some decibell rolloff
-    ///
-    /// produce an iterator that returns the gain for each speaker given the source as an input.
-    pub fn new(speakers: &'a [Speaker<S>], rolloff_db: S) -> Self {
-        assert!(speakers.len() > 0);
-        let a_coefficient = a_coefficient(rolloff_db);
-        let k_coefficient = k_coefficient(a_coefficient, speakers);
-        SpeakerGains {
-            speakers,
-            a_coefficient,
-            k_coefficient,
-            i: 0,
+    fn default() -> Self {
+        Speaker::new(S::from_f32(0.0), S::from_f32(1.0))
+    }
+}
+
+impl From<Speaker<f32>> for Speaker<f64> {
+    fn from(s: Speaker<f32>) -> Self {
+        Speaker {
+            distance: s.distance.into(),
+            weight: s.weight.into(),
         }
     }
 }
 
-impl<'a, S> Iterator for SpeakerGains<'a, S>
+/// A lossy conversion, rounding the `distance` and `weight` to the nearest `f32`.
+impl From<Speaker<f64>> for Speaker<f32> {
+    fn from(s: Speaker<f64>) -> Self {
+        Speaker {
+            distance: s.distance as f32,
+            weight: s.weight as f32,
+        }
+    }
+}
+
+#[cfg(feature = "approx")]
+impl<S> approx::AbsDiffEq for Speaker<S>
 where
-    S: Scalar,
+    S: approx::AbsDiffEq,
+    S::Epsilon: Copy,
 {
-    type Item = S;
-    fn next(&mut self) -> Option<Self::Item> {
-        let i = self.i;
-        if i >= self.speakers.len() {
-            return None;
-        }
-        self.i += 1;
-        let s = &self.speakers[i];
-        let s_r_amp = v_speaker_relative_amplitude(s, self.k_coefficient, self.a_coefficient);
-        Some(s_r_amp / s.distance)
+    type Epsilon = S::Epsilon;
+    fn default_epsilon() -> Self::Epsilon {
+        S::default_epsilon()
+    }
+    fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
+        self.distance.abs_diff_eq(&other.distance, epsilon)
+            && self.weight.abs_diff_eq(&other.weight, epsilon)
     }
 }
 
-/// The same as a regular *distance* function but applies a subtle `blur` amount.
+#[cfg(feature = "approx")]
+impl<S> approx::RelativeEq for Speaker<S>
+where
+    S: approx::RelativeEq,
+    S::Epsilon: Copy,
+{
+    fn default_max_relative() -> Self::Epsilon {
+        S::default_max_relative()
+    }
+    fn relative_eq(
+        &self,
+        other: &Self,
+        epsilon: Self::Epsilon,
+        max_relative: Self::Epsilon,
+    ) -> bool {
+        self.distance
+            .relative_eq(&other.distance, epsilon, max_relative)
+            && self
+                .weight
+                .relative_eq(&other.weight, epsilon, max_relative)
+    }
+}
+
+/// Convert a slice of speakers from one scalar type to another, e.g. when authoring in `f64` but
+/// rendering in `f32`.
+#[cfg(feature = "alloc")]
+pub fn convert_speakers<A, B>(speakers: &[Speaker<A>]) -> Vec<Speaker<B>>
+where
+    A: Copy,
+    Speaker<B>: From<Speaker<A>>,
+{
+    speakers.iter().map(|&s| s.into()).collect()
+}
+
+/// Solve for the weight of each speaker that equalises its contribution, given the SPL measured at
+/// a reference microphone with each speaker driven individually at the same level.
 ///
-/// From the paper: "In 2D space, blur can be understood as a vertical displacement between source
-/// and speakers. The larger ` gets, the less the source will be able to gravitate towards one
-/// speaker only."
+/// Each weight is the linear correction `10^((target_spl_db - measured_spl_db) / 20)`, such that a
+/// speaker measuring 6dB hotter than the target receives a weight of `≈0.5`. As weights are
+/// relative to one another, the choice of `target_spl_db` only scales the weights uniformly.
 ///
-/// A non-zero blur will ensure that the distance is greater than `0.0` and that we never divide by 0.0.
-pub fn blurred_distance_2<S>(source: [S; 2], speaker: [S; 2], blur: S) -> S
+/// To trim each speaker without affecting the spatial distribution of the gains, prefer
+/// `SpeakerGains::with_calibration`.
+#[cfg(feature = "alloc")]
+pub fn calibrate_weights_from_spl<S>(measured_spl_db: &[S], target_spl_db: S) -> Vec<S>
 where
     S: Scalar,
 {
-    let x = speaker[0] - source[0];
-    let y = speaker[1] - source[1];
-    x * x + y * y + blur * blur
+    let ten = S::from_f32(10.0);
+    let twenty = S::from_f32(20.0);
+    measured_spl_db
+        .iter()
+        .map(|&measured| ten.pow((target_spl_db - measured) / twenty))
+        .collect()
 }
 
-/// The relative amplitude for a speaker where:
+/// A speaker described by its position within a 2D space rather than its distance from a source.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PositionedSpeaker<S = DefaultScalar> {
+    /// The speaker's position within the space.
+    pub position: [S; 2],
+    /// The weight applied to the speaker, compared to all other speakers.
+    pub weight: S,
+}
+
+impl<S> PositionedSpeaker<S>
+where
+    S: Scalar,
+{
+    /// Produce the `Speaker` describing this speaker's blurred distance from the given source.
+    pub fn speaker(&self, source: [S; 2], blur: S) -> Speaker<S> {
+        let distance = blurred_distance_2(source, self.position, blur).pow(S::from_f32(0.5));
+        Speaker::new(distance, self.weight)
+    }
+}
+
+/// A directional speaker whose contribution is reduced as the source moves away from its axis.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DirectiveSpeaker<S = DefaultScalar> {
+    /// The speaker's position within the space.
+    pub position: [S; 2],
+    /// The direction in which the speaker is facing. Need not be normalised.
+    pub facing: [S; 2],
+    /// The weight applied to the speaker, compared to all other speakers.
+    pub weight: S,
+}
+
+impl<S> DirectiveSpeaker<S>
+where
+    S: Scalar,
+{
+    /// The cardioid directivity factor `0.5 * (1 + cos θ)` for the given source, where `θ` is the
+    /// angle between the speaker's facing direction and the direction from the speaker to the
+    /// source.
+    ///
+    /// The factor is `1.0` for a source directly in front of the speaker and `0.0` for a source
+    /// directly behind. A source at the speaker's position (or a zero `facing` vector) yields
+    /// `1.0`.
+    pub fn directivity(&self, source: [S; 2]) -> S {
+        let zero = S::from_f32(0.0);
+        let half = S::from_f32(0.5);
+        let to_source = [source[0] - self.position[0], source[1] - self.position[1]];
+        let dot = to_source[0] * self.facing[0] + to_source[1] * self.facing[1];
+        let mag2 = blurred_distance_2([zero, zero], to_source, zero)
+            * blurred_distance_2([zero, zero], self.facing, zero);
+        if mag2 == zero {
+            return S::from_f32(1.0);
+        }
+        let cos = dot / mag2.pow(half);
+        half * (S::from_f32(1.0) + cos)
+    }
+
+    /// Produce the `Speaker` describing this speaker's blurred distance from the given source,
+    /// with the directivity folded into its weight.
+    pub fn speaker(&self, source: [S; 2], blur: S) -> Speaker<S> {
+        let distance = blurred_distance_2(source, self.position, blur).pow(S::from_f32(0.5));
+        Speaker::new(distance, self.weight * self.directivity(source))
+    }
+}
+
+/// A listener within the space, decoupled from the source position used to compute distances.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Listener<S = DefaultScalar> {
+    /// The listener's position within the space.
+    pub position: [S; 2],
+}
+
+impl<S> Listener<S>
+where
+    S: Scalar,
+{
+    /// The vector from the listener to each of the given speakers.
+    #[cfg(feature = "alloc")]
+    pub fn speaker_vectors(&self, speakers: &[PositionedSpeaker<S>]) -> Vec<[S; 2]> {
+        let [lx, ly] = self.position;
+        speakers
+            .iter()
+            .map(|s| [s.position[0] - lx, s.position[1] - ly])
+            .collect()
+    }
+}
+
+/// A similarity transform for mapping positions between two 2D coordinate frames.
 ///
-/// - `k` is a coefficient depending on the position of the source and all speakers
-/// - `a` is a coefficient calculated from the rolloff in decibels per doubling distance.
+/// Points are first scaled, then rotated about the origin, then translated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform2<S = DefaultScalar> {
+    /// The anti-clockwise rotation in radians.
+    pub rotation: S,
+    /// The uniform scale.
+    pub scale: S,
+    /// The translation applied after scaling and rotation.
+    pub translation: [S; 2],
+}
+
+#[cfg(feature = "full")]
+impl<S> Transform2<S>
+where
+    S: Scalar + Float,
+{
+    /// The transform that leaves all points unchanged.
+    pub fn identity() -> Self {
+        Transform2 {
+            rotation: S::zero(),
+            scale: S::one(),
+            translation: [S::zero(); 2],
+        }
+    }
+
+    /// Map the given point from the source frame into the target frame.
+    pub fn apply(&self, [x, y]: [S; 2]) -> [S; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (x, y) = (x * self.scale, y * self.scale);
+        [
+            x * cos - y * sin + self.translation[0],
+            x * sin + y * cos + self.translation[1],
+        ]
+    }
+
+    /// Produce a transform equivalent to applying `self` followed by `next`.
+    pub fn then(&self, next: &Self) -> Self {
+        let translation = next.apply(self.translation);
+        Transform2 {
+            rotation: self.rotation + next.rotation,
+            scale: self.scale * next.scale,
+            translation,
+        }
+    }
+}
+
+/// A fixed-size layout of speakers whose count is known at compile time.
 ///
-/// The speaker's `distance` field must be greater than zero or the result will be NaN.
-pub fn v_speaker_relative_amplitude<S>(speaker: &Speaker<S>, k: S, a: S) -> S
+/// Gains are produced as arrays of the same length, making mismatched gain buffers impossible for
+/// fixed rigs.
+#[derive(Copy, Clone, Debug)]
+pub struct Field<S, const N: usize> {
+    /// The speakers within the field.
+    pub speakers: [Speaker<S>; N],
+}
+
+impl<S, const N: usize> Field<S, N>
 where
     S: Scalar,
 {
-    k * speaker.weight / ((speaker.distance + speaker.distance) * a)
+    /// Produce the gain for each speaker within the field.
+    ///
+    /// **Panics** if `N` is `0`.
+    pub fn gains(&self, rolloff_db: S) -> [S; N] {
+        let mut gains = [S::from_f32(0.0); N];
+        for (g, gain) in gains
+            .iter_mut()
+            .zip(SpeakerGains::new(&self.speakers, rolloff_db))
+        {
+            *g = gain;
+        }
+        gains
+    }
 }
 
-/// A coefficient calculated from the rolloff `r` in decibels per doubling of distance.
-///
-/// A rolloff of 6dB equals the inverse distance law for sound propagataing in a free field.
+impl<'a, S, const N: usize> IntoIterator for &'a Field<S, N> {
+    type Item = &'a Speaker<S>;
+    type IntoIter = core::slice::Iter<'a, Speaker<S>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.speakers.iter()
+    }
+}
+
+/// A reusable buffer of speaker distances for a fixed layout of positioned speakers.
 ///
-/// For closed or semi-closed environments `r` will generally be lower, in the range 3-5dB, and
-/// depend on reflections and reverberation.
-pub fn a_coefficient<S>(rolloff_db: S) -> S
+/// This separates the distance computation (which changes with the source position) from the
+/// speaker definitions, allowing many source positions to be evaluated without re-allocating.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub struct DistanceBuffer<S = DefaultScalar> {
+    positions: Vec<[S; 2]>,
+    speakers: Vec<Speaker<S>>,
+    /// The blur applied to each distance.
+    pub blur: S,
+}
+
+#[cfg(feature = "alloc")]
+impl<S> DistanceBuffer<S>
 where
     S: Scalar,
 {
-    S::from(10f32).pow(-rolloff_db / S::from(20.0))
+    /// Create a buffer for the given layout.
+    ///
+    /// The distances are initially measured from the origin.
+    pub fn new(speakers: &[PositionedSpeaker<S>], blur: S) -> Self {
+        let zero = S::from_f32(0.0);
+        let positions = speakers.iter().map(|s| s.position).collect();
+        let speakers = speakers_2(speakers, [zero, zero], blur);
+        DistanceBuffer {
+            positions,
+            speakers,
+            blur,
+        }
+    }
+
+    /// Refill the distances in place for the given source position.
+    pub fn update(&mut self, source: [S; 2]) {
+        for (s, &position) in self.speakers.iter_mut().zip(&self.positions) {
+            s.distance = blurred_distance_2(source, position, self.blur).pow(S::from_f32(0.5));
+        }
+    }
+
+    /// The speakers described by their distance from the last source position.
+    pub fn speakers(&self) -> &[Speaker<S>] {
+        &self.speakers
+    }
+
+    /// Produce the gains for the last source position.
+    ///
+    /// **Panics** if the buffer contains no speakers.
+    pub fn gains(&self, rolloff_db: S) -> SpeakerGains<'_, S> {
+        SpeakerGains::new(&self.speakers, rolloff_db)
+    }
 }
 
-/// `k` is a coefficient depending on the position of the source and all speakers.
+/// A builder for configuring the DBAP calculation once and computing gains directly from speaker
+/// positions.
 ///
-/// Returns `0.0` if all speakers had a weight or distance of `0.0`.
+/// ```
+/// # #[cfg(feature = "alloc")] {
+/// use dbap::{DbapBuilder, Normalization, PositionedSpeaker};
 ///
-/// **Panics** if there were no speakers in the list.
-pub fn k_coefficient<S>(a: S, speakers: &[Speaker<S>]) -> S
+/// let speakers: Vec<PositionedSpeaker> = [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]]
+///     .iter()
+///     .map(|&position| PositionedSpeaker { position, weight: 1.0 })
+///     .collect();
+/// let gains = DbapBuilder::new()
+///     .rolloff_db(6.0)
+///     .blur(0.1)
+///     .nearest(3)
+///     .normalization(Normalization::ConstantPower)
+///     .gains_at_2(&speakers, [2.0, 3.0]);
+/// assert_eq!(gains.len(), 4);
+/// assert_eq!(gains[2], 0.0);
+/// let power: f32 = gains.iter().map(|g| g * g).sum();
+/// assert!((power - 1.0).abs() < 1e-6);
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DbapBuilder<S = DefaultScalar> {
+    /// The rolloff in decibels per doubling of distance. Defaults to `6.0`.
+    pub rolloff_db: S,
+    /// The blur applied to each speaker distance. Defaults to `0.0`.
+    pub blur: S,
+    /// The normalisation applied to the resulting gains, if any. Defaults to `None`.
+    pub normalization: Option<Normalization<S>>,
+    /// If set, only the `n` speakers nearest to the source receive gain. Defaults to `None`.
+    pub nearest: Option<usize>,
+}
+
+impl<S> DbapBuilder<S>
 where
     S: Scalar,
 {
-    let zero = S::from(0f32);
-    let sum = speakers
-        .iter()
-        .map(|s| {
-            if s.distance == zero {
-                return zero;
+    /// A builder with the default configuration.
+    pub fn new() -> Self {
+        DbapBuilder {
+            rolloff_db: S::from_f32(6.0),
+            blur: S::from_f32(0.0),
+            normalization: None,
+            nearest: None,
+        }
+    }
+
+    /// Specify the rolloff in decibels per doubling of distance.
+    pub fn rolloff_db(self, rolloff_db: S) -> Self {
+        DbapBuilder { rolloff_db, ..self }
+    }
+
+    /// Specify the blur applied to each speaker distance.
+    pub fn blur(self, blur: S) -> Self {
+        DbapBuilder { blur, ..self }
+    }
+
+    /// Specify the normalisation applied to the resulting gains.
+    pub fn normalization(self, normalization: Normalization<S>) -> Self {
+        let normalization = Some(normalization);
+        DbapBuilder {
+            normalization,
+            ..self
+        }
+    }
+
+    /// Only the `n` speakers nearest to the source receive gain, with all others excluded from the
+    /// calculation.
+    pub fn nearest(self, n: usize) -> Self {
+        let nearest = Some(n);
+        DbapBuilder { nearest, ..self }
+    }
+
+    /// Compute the gain of each speaker for a source at the given position.
+    ///
+    /// **Panics** if `speakers` is empty.
+    #[cfg(feature = "alloc")]
+    pub fn gains_at_2(&self, speakers: &[PositionedSpeaker<S>], source: [S; 2]) -> Vec<S> {
+        let mut spkrs = speakers_2(speakers, source, self.blur);
+        self.gains(&mut spkrs, a_coefficient(self.rolloff_db))
+    }
+
+    /// Consume the builder, producing a closure that computes the gain of each speaker for a
+    /// source at the given position.
+    ///
+    /// The speaker positions and the `a` coefficient are captured once, and the buffer of speaker
+    /// distances is re-used between calls. Each call produces the same gains as `gains_at_2`.
+    ///
+    /// **Panics** if `speakers` is empty.
+    #[cfg(feature = "alloc")]
+    pub fn build_query_2(self, speakers: &[PositionedSpeaker<S>]) -> impl FnMut([S; 2]) -> Vec<S> {
+        assert!(!speakers.is_empty());
+        let positions = speakers.to_vec();
+        let a = a_coefficient(self.rolloff_db);
+        let mut spkrs = Vec::with_capacity(positions.len());
+        move |source| {
+            spkrs.clear();
+            spkrs.extend(positions.iter().map(|s| s.speaker(source, self.blur)));
+            self.gains(&mut spkrs, a)
+        }
+    }
+
+    // Apply the nearest selection and normalisation to the gains of the given speakers.
+    #[cfg(feature = "alloc")]
+    fn gains(&self, spkrs: &mut [Speaker<S>], a_coefficient: S) -> Vec<S> {
+        if let Some(n) = self.nearest {
+            let selected = NearestSelector::new(n, 0).select(spkrs).to_vec();
+            for (i, s) in spkrs.iter_mut().enumerate() {
+                if !selected.contains(&i) {
+                    s.weight = S::from_f32(0.0);
+                }
             }
-            let w2 = s.weight * s.weight;
-            let d2 = s.distance * s.distance;
-            w2 / d2
-        })
-        .sum();
-    if sum == zero {
-        zero
-    } else {
-        S::from(2.0) * a / sum
+        }
+        let gains = SpeakerGains::with_a_coefficient(spkrs, a_coefficient);
+        match self.normalization {
+            Some(mode) => gains.normalized(mode),
+            None => gains.collect(),
+        }
     }
 }
 
-#[test]
-fn speaker_gains() {
-    fn magnitude2<S>([x, y]: [S; 2]) -> S
-    where
-        S: Copy + Add<S, Output = S> + Mul<S, Output = S>,
-    {
-        x * x + y * y
+impl<S> Default for DbapBuilder<S>
+where
+    S: Scalar,
+{
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    fn distance2<S>([ax, ay]: [S; 2], [bx, by]: [S; 2]) -> S
-    where
-        S: Copy + Add<S, Output = S> + Mul<S, Output = S> + Sub<S, Output = S>,
-    {
-        magnitude2([bx - ax, by - ay])
+/// An iterator yielding the gain for each given speaker, given their weights and distance from the
+/// source position.
+#[derive(Clone)]
+pub struct SpeakerGains<'a, S = DefaultScalar> {
+    speakers: &'a [Speaker<S>],
+    a_coefficient: S,
+    k_coefficient: S,
+    min_distance: S,
+    master: S,
+    calibration_db: &'a [S],
+    i: usize,
+}
+
+impl<'a, S> SpeakerGains<'a, S>
+where
+    S: Scalar,
+{
+    /// Given:
+    ///
+    /// - a list of speaker distances from the virtual source:
+    /// - weights for each of those speakers and
+    /// - some decibell rolloff
+    ///
+    /// produce an iterator that returns the gain for each speaker given the source as an input.
+    ///
+    /// Weights are relative to one another: scaling the weights of all speakers by the same
+    /// positive constant leaves the resulting gains unchanged.
+    ///
+    /// In the case of a single speaker with a non-zero weight and distance, the gain is always
+    /// `1.0` regardless of its weight, distance or the rolloff. A lone speaker with a weight of
+    /// `0.0` receives a gain of `0.0`.
+    ///
+    /// Note that the `a` coefficient derived from the rolloff scales both `k` and each relative
+    /// amplitude, cancelling out of the resulting gains, such that each gain is proportional to
+    /// `wᵢ / dᵢ²` for any rolloff. See `modelled_gains` for gains that the rolloff shapes.
+    ///
+    /// **Panics** if `speakers` is empty.
+    pub fn new(speakers: &'a [Speaker<S>], rolloff_db: S) -> Self {
+        Self::with_a_coefficient(speakers, a_coefficient(rolloff_db))
     }
 
-    let src = [5f64, 5.0];
-    let speaker = |v: [f64; 2], w| Speaker {
-        distance: distance2(v, src).sqrt(),
-        weight: w,
-    };
-    let a = speaker([0.0, 0.0], 1.0);
-    let b = speaker([10.0, 0.0], 1.0);
-    let c = speaker([10.0, 10.0], 1.0);
-    let d = speaker([0.0, 10.0], 1.0);
-    let spkrs = vec![a, b, c, d];
-    let r = 6.0; // free-field rolloff db.
-    let gains = SpeakerGains::new(&spkrs, r).collect::<Vec<_>>();
-    let g = gains[0];
+    /// The same as `new` for the free-field rolloff of 6dB, but skips the `pow` within
+    /// `a_coefficient` by using an `a` coefficient of exactly `0.5`.
+    ///
+    /// Strictly, an `a` of `0.5` corresponds to a rolloff of `20·log₁₀(2) ≈ 6.02dB`, while 6dB
+    /// gives `a ≈ 0.5012`. The `a` coefficient cancels out of the resulting gains however, so this
+    /// is equivalent to `SpeakerGains::new(speakers, 6.0)` up to floating point rounding.
+    ///
+    /// **Panics** if `speakers` is empty.
+    pub fn free_field(speakers: &'a [Speaker<S>]) -> Self {
+        Self::with_a_coefficient(speakers, S::from_f32(0.5))
+    }
+
+    /// The same as `new`, but accepts an `a` coefficient pre-computed via
+    /// `a_coefficient(rolloff_db)` such that only `k` is computed.
+    ///
+    /// This avoids re-computing `a` when panning to many different subsets of speakers that share
+    /// the same rolloff, e.g. the speakers of each zone.
+    ///
+    /// **Panics** if `speakers` is empty.
+    pub fn with_a_coefficient(speakers: &'a [Speaker<S>], a_coefficient: S) -> Self {
+        assert!(!speakers.is_empty());
+        let k_coefficient = k_coefficient(a_coefficient, speakers);
+        SpeakerGains {
+            speakers,
+            a_coefficient,
+            k_coefficient,
+            min_distance: S::from_f32(0.0),
+            master: S::from_f32(1.0),
+            calibration_db: &[],
+            i: 0,
+        }
+    }
+
+    /// The same as `new`, but returns an error if the field is degenerate.
+    ///
+    /// A field is degenerate if every speaker has a weight or distance of `0.0`, in which case the
+    /// `k` coefficient is `0.0` and every gain would be `0.0`. This allows callers to distinguish a
+    /// silent field from a legitimately quiet one and to substitute a fallback.
+    ///
+    /// **Panics** if `speakers` is empty.
+    pub fn try_new(speakers: &'a [Speaker<S>], rolloff_db: S) -> Result<Self, DegenerateField> {
+        let gains = Self::new(speakers, rolloff_db);
+        match gains.is_degenerate() {
+            true => Err(DegenerateField),
+            false => Ok(gains),
+        }
+    }
+
+    /// Restart iteration from the first speaker without re-computing the `a` and `k` coefficients.
+    pub fn reset(&mut self) {
+        self.i = 0;
+    }
+
+    /// Whether or not every speaker has a weight or distance of `0.0`, resulting in silence.
+    pub fn is_degenerate(&self) -> bool {
+        self.k_coefficient == S::from_f32(0.0)
+    }
+
+    /// Compute the gains for speakers whose weights are described by a probability distribution.
+    ///
+    /// The `weights` are first normalised such that they sum to `1.0`, allowing generative logic
+    /// to drive the weights with an unnormalised distribution. If the weights sum to `0.0` they are
+    /// left unchanged.
+    ///
+    /// **Panics** if `distances` is empty or if `weights` is not the same length as `distances`.
+    #[cfg(feature = "alloc")]
+    pub fn from_weight_distribution(distances: &[S], weights: &[S], rolloff_db: S) -> Vec<S> {
+        assert_eq!(distances.len(), weights.len());
+        let mut weights = weights.to_vec();
+        normalize(&mut weights, Normalization::ConstantAmplitude);
+        let spkrs: Vec<_> = distances
+            .iter()
+            .zip(weights)
+            .map(|(&distance, weight)| Speaker::new(distance, weight))
+            .collect();
+        SpeakerGains::new(&spkrs, rolloff_db).collect()
+    }
+
+    /// Clamp the distance of each speaker such that it is never less than `min_distance`.
+    ///
+    /// The `k` coefficient is re-computed using the clamped distances. This can be used to avoid
+    /// excessive gains for sources that lie very close to a speaker.
+    pub fn with_min_distance(self, min_distance: S) -> Self {
+        let mut gains = SpeakerGains {
+            min_distance,
+            ..self
+        };
+        let spkrs = gains.speakers.iter().map(|s| gains.effective_speaker(s));
+        let (mean_weight, sum) = k_sums(spkrs);
+        gains.k_coefficient = k_from_sums(gains.a_coefficient, mean_weight, sum);
+        gains
+    }
+
+    /// Apply a master trim to every gain, or silence every gain if `muted` is `true`.
+    ///
+    /// The master is applied after any normalisation, such that `normalized` produces the
+    /// normalised gains scaled by `trim`.
+    pub fn with_master(self, trim: S, muted: bool) -> Self {
+        let master = match muted {
+            true => S::from_f32(0.0),
+            false => trim,
+        };
+        SpeakerGains { master, ..self }
+    }
+
+    /// Apply a fixed level trim in dB to the gain of each speaker, e.g. to compensate for the
+    /// sensitivity of its driver, where `calibration_db[i]` trims the speaker at index `i`. A trim
+    /// of `0.0` leaves the speaker's gain unchanged.
+    ///
+    /// Unlike the speakers' weights, which shape the spatial distribution of the gains and
+    /// contribute to the `k` coefficient, the trims are applied after panning and do not affect the
+    /// gains of any other speaker.
+    ///
+    /// The trims are held by the `SpeakerGains` rather than by each `Speaker`, such that `Speaker`
+    /// remains a plain distance and weight pair that can be constructed from a struct literal.
+    ///
+    /// **Panics** if `calibration_db` is not the same length as the slice of speakers.
+    pub fn with_calibration(self, calibration_db: &'a [S]) -> Self {
+        assert_eq!(self.speakers.len(), calibration_db.len());
+        SpeakerGains {
+            calibration_db,
+            ..self
+        }
+    }
+
+    /// The distance of each speaker as used within the gain computation, after clamping to the
+    /// minimum distance.
+    ///
+    /// Note that any `blur` is applied prior to constructing the `Speaker`s and is already
+    /// reflected in their `distance`.
+    #[cfg(feature = "alloc")]
+    pub fn effective_distances(&self) -> Vec<S> {
+        self.speakers
+            .iter()
+            .map(|s| self.effective_speaker(s).distance)
+            .collect()
+    }
+
+    /// Produce a human-readable table describing the distance, weight and resulting gain of each
+    /// speaker, with one row per speaker and aligned columns.
+    ///
+    /// Speakers are identified by their index.
+    ///
+    /// The table always describes all speakers, regardless of how many gains have been consumed.
+    #[cfg(feature = "alloc")]
+    pub fn debug_table(&self) -> String
+    where
+        S: core::fmt::Display,
+    {
+        self.debug_table_with_names(&[])
+    }
+
+    /// The same as `debug_table`, but identifies each speaker by the name at the same index within
+    /// `names`, e.g. names loaded from a configuration file at runtime.
+    ///
+    /// If `names` is empty, each speaker is identified by its index.
+    ///
+    /// **Panics** if `names` is neither empty nor the same length as the speakers.
+    #[cfg(feature = "alloc")]
+    pub fn debug_table_with_names(&self, names: &[&str]) -> String
+    where
+        S: core::fmt::Display,
+    {
+        assert!(names.is_empty() || names.len() == self.speakers.len());
+        let gains = SpeakerGains {
+            i: 0,
+            ..self.clone()
+        };
+        let header = ["speaker", "distance", "weight", "gain"];
+        let rows: Vec<[String; 4]> = gains
+            .enumerate()
+            .map(|(i, gain)| {
+                let s = &self.speakers[i];
+                let name = names.get(i).map(|&n| String::from(n));
+                [
+                    name.unwrap_or_else(|| i.to_string()),
+                    s.distance.to_string(),
+                    s.weight.to_string(),
+                    gain.to_string(),
+                ]
+            })
+            .collect();
+        let mut widths = [0; 4];
+        for (w, h) in widths.iter_mut().zip(&header) {
+            *w = h.len();
+        }
+        for row in &rows {
+            for (w, cell) in widths.iter_mut().zip(row) {
+                *w = (*w).max(cell.chars().count());
+            }
+        }
+        let mut table = String::new();
+        let mut write_row = |cells: &[&str]| {
+            let line: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &w)| format!("{:>w$}", cell, w = w))
+                .collect();
+            table.push_str(&line.join(" | "));
+            table.push('\n');
+        };
+        write_row(&header);
+        for row in &rows {
+            let cells: Vec<&str> = row.iter().map(|s| &s[..]).collect();
+            write_row(&cells);
+        }
+        table
+    }
+
+    // The linear factor `10^(calibration_db / 20)` by which the gain of the speaker at the given
+    // index is scaled.
+    fn calibration_factor(&self, i: usize) -> S {
+        match self.calibration_db.get(i) {
+            Some(&db) if db != S::from_f32(0.0) => S::from_f32(10.0).pow(db / S::from_f32(20.0)),
+            _ => S::from_f32(1.0),
+        }
+    }
+
+    // The speaker as used within the gain computation.
+    fn effective_speaker(&self, s: &Speaker<S>) -> Speaker<S> {
+        match s.distance < self.min_distance {
+            true => Speaker {
+                distance: self.min_distance,
+                ..*s
+            },
+            false => *s,
+        }
+    }
+
+    /// Consume the remaining gains, producing each speaker's share of their total as a fraction in
+    /// the range `0.0..=1.0`.
+    ///
+    /// The resulting values always sum to `1.0` regardless of any normalisation applied to the
+    /// gains. If the gains sum to `0.0`, all contributions are `0.0`.
+    #[cfg(feature = "alloc")]
+    pub fn contributions(self) -> Vec<S> {
+        let gains: Vec<S> = self.collect();
+        let zero = S::from_f32(0.0);
+        let total: S = gains.iter().cloned().sum();
+        if total == zero {
+            return gains.iter().map(|_| zero).collect();
+        }
+        gains.into_iter().map(|g| g / total).collect()
+    }
+
+    /// Consume the remaining gains, adding each to the element of `out` at the same index as its
+    /// speaker.
+    ///
+    /// This is useful for mixing the gains of many sources into a single buffer without
+    /// intermediate allocations.
+    ///
+    /// **Panics** if `out` is not the same length as the slice of speakers.
+    pub fn accumulate_into(self, out: &mut [S]) {
+        assert_eq!(self.speakers.len(), out.len());
+        let start = self.i;
+        for (o, g) in out[start..].iter_mut().zip(self) {
+            *o = *o + g;
+        }
+    }
+
+    /// Consume the gains, producing an iterator yielding each remaining gain alongside the distance
+    /// of its speaker as a `(distance, gain)` pair.
+    ///
+    /// The distance is the effective distance used within the gain computation (see
+    /// `effective_distances`).
+    pub fn with_distance(self) -> WithDistance<'a, S> {
+        WithDistance { gains: self }
+    }
+
+    /// Consume the gains, producing an iterator yielding each remaining gain alongside the speaker
+    /// from which it was produced as a `(speaker, gain)` pair.
+    ///
+    /// The speaker is the original speaker, rather than the effective speaker after clamping to
+    /// the minimum distance.
+    pub fn zip_speakers(self) -> ZipSpeakers<'a, S> {
+        ZipSpeakers { gains: self }
+    }
+
+    /// Consume the remaining gains, producing them normalised using the given strategy.
+    ///
+    /// The uncalibrated gains are normalised, after which each speaker's calibration trim and the
+    /// master are applied, such that calibrated speakers remain trimmed relative to the others.
+    ///
+    /// See `Normalization::PinNearest` for how the nearest speaker is determined.
+    #[cfg(feature = "alloc")]
+    pub fn normalized(self, mode: Normalization<S>) -> Vec<S> {
+        let start = self.i;
+        let distances = self.effective_distances();
+        let mut gains: Vec<S> = self
+            .clone()
+            .relative_amplitudes()
+            .zip(&distances[start..])
+            .map(|(v, &d)| v / d)
+            .collect();
+        match mode {
+            Normalization::PinNearest(target) if !gains.is_empty() => {
+                let mut nearest = 0;
+                for (i, &d) in distances[start..].iter().enumerate() {
+                    if d < distances[start + nearest] {
+                        nearest = i;
+                    }
+                }
+                pin_gain(&mut gains, nearest, target);
+            }
+            mode => normalize(&mut gains, mode),
+        }
+        for (i, g) in gains.iter_mut().enumerate() {
+            *g = *g * self.calibration_factor(start + i) * self.master;
+        }
+        gains
+    }
+
+    /// Consume the remaining gains, producing them scaled such that their total power `Σ(gᵢ²)`
+    /// equals `target_db` dBFS, i.e. `10·log₁₀(Σ(gᵢ²)) = target_db`.
+    ///
+    /// Each gain is yielded in linear form. Like `normalized`, the calibration trims and the master
+    /// are applied after the normalisation rather than being erased by it, such that the total
+    /// power only equals `target_db` where neither is set. Gains that are all `0.0` are left
+    /// unchanged.
+    #[cfg(feature = "alloc")]
+    pub fn normalized_to_db_power(self, target_db: S) -> Vec<S> {
+        let scale = S::from_f32(10.0).pow(target_db / S::from_f32(20.0));
+        let mut gains = self.normalized(Normalization::ConstantPower);
+        for g in gains.iter_mut() {
+            *g = *g * scale;
+        }
+        gains
+    }
+
+    /// Consume the remaining gains, producing each alongside the index of its speaker as an
+    /// `(index, gain)` pair, sorted from the loudest speaker to the quietest.
+    ///
+    /// Speakers with equal gains remain in ascending order of index.
+    #[cfg(feature = "alloc")]
+    pub fn ranked(self) -> Vec<(usize, S)> {
+        let start = self.i;
+        let mut ranked: Vec<_> = self.enumerate().map(|(i, g)| (start + i, g)).collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(core::cmp::Ordering::Equal));
+        ranked
+    }
+
+    /// Reduce the remaining gains to a single value in a single pass without collecting them, e.g.
+    /// for metering.
+    ///
+    /// This is equivalent to `Iterator::fold`. See `peak`, `gain_sum` and `rms` for common
+    /// reductions.
+    pub fn fold_gains<B, F>(self, init: B, f: F) -> B
+    where
+        F: FnMut(B, S) -> B,
+    {
+        self.fold(init, f)
+    }
+
+    /// The greatest of the remaining gains, or `0.0` if there are none.
+    pub fn peak(self) -> S {
+        self.fold_gains(S::from_f32(0.0), |max, g| if g > max { g } else { max })
+    }
+
+    /// The sum of the remaining gains, equivalent to `Iterator::sum`.
+    pub fn gain_sum(self) -> S {
+        self.fold_gains(S::from_f32(0.0), |sum, g| sum + g)
+    }
+
+    /// The root mean square `sqrt(Σ(gᵢ²) / n)` of the remaining gains, or `0.0` if there are none.
+    pub fn rms(self) -> S {
+        let zero = S::from_f32(0.0);
+        let (n, sum) = self.fold_gains((0, zero), |(n, sum), g| (n + 1, sum + g * g));
+        mean(sum, n).pow(S::from_f32(0.5))
+    }
+
+    /// Consume the gains, producing an iterator yielding each remaining gain alongside the
+    /// propagation delay in samples from the source to its speaker as a `(gain, delay_samples)`
+    /// pair.
+    ///
+    /// The delay is `distance / speed_of_sound * sample_rate`, where `distance` is the effective
+    /// distance and `speed_of_sound` is expressed in the same distance units per second.
+    pub fn with_delays(self, speed_of_sound: S, sample_rate: S) -> WithDelays<'a, S> {
+        WithDelays {
+            distances: self.with_distance(),
+            samples_per_unit: sample_rate / speed_of_sound,
+        }
+    }
+
+    /// Consume the gains, producing an iterator yielding the relative amplitude of each remaining
+    /// speaker *before* the final division by the speaker's distance.
+    ///
+    /// Each value is `v_speaker_relative_amplitude` of the effective speaker, such that dividing it
+    /// by the speaker's distance reproduces its gain prior to any calibration trim or master.
+    pub fn relative_amplitudes(self) -> RelativeAmplitudes<'a, S> {
+        RelativeAmplitudes { gains: self }
+    }
+
+    /// Consume the gains, producing an iterator yielding each gain as a dBFS attenuation.
+    ///
+    /// Each gain is converted via `20 * log10(gain) - headroom_db` and clamped so that it is never
+    /// lower than `floor_db`. A gain of `0.0` yields `floor_db`.
+    #[cfg(feature = "full")]
+    pub fn to_dbfs(self, headroom_db: S, floor_db: S) -> Dbfs<'a, S> {
+        Dbfs {
+            gains: self,
+            headroom_db,
+            floor_db,
+        }
+    }
+}
+
+/// Returned by `SpeakerGains::try_new` when every speaker has a weight or distance of `0.0`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DegenerateField;
+
+impl core::fmt::Display for DegenerateField {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "every speaker has a weight or distance of zero")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DegenerateField {}
+
+impl<'a, S> Iterator for SpeakerGains<'a, S>
+where
+    S: Scalar,
+{
+    type Item = S;
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.i;
+        if i >= self.speakers.len() {
+            return None;
+        }
+        self.i += 1;
+        let s = self.effective_speaker(&self.speakers[i]);
+        let s_r_amp = v_speaker_relative_amplitude(&s, self.k_coefficient, self.a_coefficient);
+        Some(s_r_amp / s.distance * self.calibration_factor(i) * self.master)
+    }
+}
+
+/// An iterator yielding the gain of each speaker alongside its distance.
+///
+/// Produced via `SpeakerGains::with_distance`.
+#[derive(Clone)]
+pub struct WithDistance<'a, S = DefaultScalar> {
+    gains: SpeakerGains<'a, S>,
+}
+
+impl<'a, S> Iterator for WithDistance<'a, S>
+where
+    S: Scalar,
+{
+    type Item = (S, S);
+    fn next(&mut self) -> Option<Self::Item> {
+        let g = &self.gains;
+        let distance = g.effective_speaker(g.speakers.get(g.i)?).distance;
+        self.gains.next().map(|gain| (distance, gain))
+    }
+}
+
+/// An iterator yielding the gain of each speaker alongside the speaker itself.
+///
+/// Produced via `SpeakerGains::zip_speakers`.
+#[derive(Clone)]
+pub struct ZipSpeakers<'a, S = DefaultScalar> {
+    gains: SpeakerGains<'a, S>,
+}
+
+impl<'a, S> Iterator for ZipSpeakers<'a, S>
+where
+    S: Scalar,
+{
+    type Item = (&'a Speaker<S>, S);
+    fn next(&mut self) -> Option<Self::Item> {
+        let speaker = self.gains.speakers.get(self.gains.i)?;
+        self.gains.next().map(|gain| (speaker, gain))
+    }
+}
+
+/// An iterator yielding the gain of each speaker alongside its propagation delay in samples.
+///
+/// Produced via `SpeakerGains::with_delays`.
+#[derive(Clone)]
+pub struct WithDelays<'a, S = DefaultScalar> {
+    distances: WithDistance<'a, S>,
+    samples_per_unit: S,
+}
+
+impl<'a, S> Iterator for WithDelays<'a, S>
+where
+    S: Scalar,
+{
+    type Item = (S, S);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (distance, gain) = self.distances.next()?;
+        Some((gain, distance * self.samples_per_unit))
+    }
+}
+
+/// An iterator yielding the relative amplitude `v` of each speaker prior to division by the
+/// speaker's distance.
+///
+/// Produced via `SpeakerGains::relative_amplitudes`.
+#[derive(Clone)]
+pub struct RelativeAmplitudes<'a, S = DefaultScalar> {
+    gains: SpeakerGains<'a, S>,
+}
+
+impl<'a, S> Iterator for RelativeAmplitudes<'a, S>
+where
+    S: Scalar,
+{
+    type Item = S;
+    fn next(&mut self) -> Option<Self::Item> {
+        let g = &mut self.gains;
+        let s = g.effective_speaker(g.speakers.get(g.i)?);
+        g.i += 1;
+        Some(v_speaker_relative_amplitude(
+            &s,
+            g.k_coefficient,
+            g.a_coefficient,
+        ))
+    }
+}
+
+/// An iterator yielding the gain of each speaker as a dBFS attenuation.
+///
+/// Produced via `SpeakerGains::to_dbfs`.
+#[cfg(feature = "full")]
+#[derive(Clone)]
+pub struct Dbfs<'a, S = DefaultScalar> {
+    gains: SpeakerGains<'a, S>,
+    headroom_db: S,
+    floor_db: S,
+}
+
+#[cfg(feature = "full")]
+impl<'a, S> Iterator for Dbfs<'a, S>
+where
+    S: Scalar + Float,
+{
+    type Item = S;
+    fn next(&mut self) -> Option<Self::Item> {
+        let gain = self.gains.next()?;
+        let db = S::from_f32(20.0) * gain.log10() - self.headroom_db;
+        Some(db.max(self.floor_db))
+    }
+}
+
+/// A layout of speakers that maintains a running `k` coefficient as speakers are added, avoiding a
+/// full re-computation for each addition.
+///
+/// `k` depends on the mean non-zero weight and the sum `Σ(wᵢ² / dᵢ²)` over all speakers, allowing
+/// each addition to update the coefficient in `O(1)`.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub struct IncrementalField<S = DefaultScalar> {
+    speakers: Vec<Speaker<S>>,
+    a_coefficient: S,
+    weight_count: usize,
+    weight_sum: S,
+    sum: S,
+}
+
+#[cfg(feature = "alloc")]
+impl<S> IncrementalField<S>
+where
+    S: Scalar,
+{
+    /// Create an empty field with the given rolloff.
+    pub fn new(rolloff_db: S) -> Self {
+        IncrementalField {
+            speakers: vec![],
+            a_coefficient: a_coefficient(rolloff_db),
+            weight_count: 0,
+            weight_sum: S::from_f32(0.0),
+            sum: S::from_f32(0.0),
+        }
+    }
+
+    /// Add a speaker to the field, updating the running `k` coefficient.
+    pub fn push_speaker(&mut self, speaker: Speaker<S>) {
+        if speaker.weight != S::from_f32(0.0) {
+            self.weight_count += 1;
+            self.weight_sum = self.weight_sum + speaker.weight;
+        }
+        self.sum = self.sum + k_term(&speaker);
+        self.speakers.push(speaker);
+    }
+
+    /// Remove and return the speaker at `index`, updating the running `k` coefficient without
+    /// re-computing it from the remaining speakers.
+    ///
+    /// If no speakers with a non-zero weight remain, the running sums are reset to exactly `0.0`
+    /// so that rounding error from previous updates cannot accumulate.
+    ///
+    /// **Panics** if `index` is out of bounds.
+    pub fn remove_speaker(&mut self, index: usize) -> Speaker<S> {
+        let speaker = self.speakers.remove(index);
+        let zero = S::from_f32(0.0);
+        if speaker.weight != zero {
+            self.weight_count -= 1;
+            self.weight_sum = self.weight_sum - speaker.weight;
+        }
+        self.sum = self.sum - k_term(&speaker);
+        if self.weight_count == 0 {
+            self.weight_sum = zero;
+            self.sum = zero;
+        }
+        speaker
+    }
+
+    /// The speakers within the field in the order in which they were added.
+    pub fn speakers(&self) -> &[Speaker<S>] {
+        &self.speakers
+    }
+
+    /// The `k` coefficient for the current set of speakers.
+    ///
+    /// Returns `0.0` if the field is empty or all speakers had a weight or distance of `0.0`.
+    pub fn k_coefficient(&self) -> S {
+        let mean_weight = mean(self.weight_sum, self.weight_count);
+        k_from_sums(self.a_coefficient, mean_weight, self.sum)
+    }
+
+    /// Produce the gains for the current set of speakers.
+    ///
+    /// **Panics** if the field is empty.
+    pub fn gains(&self) -> SpeakerGains<'_, S> {
+        assert!(!self.speakers.is_empty());
+        SpeakerGains {
+            speakers: &self.speakers,
+            a_coefficient: self.a_coefficient,
+            k_coefficient: self.k_coefficient(),
+            min_distance: S::from_f32(0.0),
+            master: S::from_f32(1.0),
+            calibration_db: &[],
+            i: 0,
+        }
+    }
+}
+
+/// Retains a decaying memory of previously computed gains, smearing fast source movements into a
+/// trail across the speakers.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub struct TrailBuffer<S = DefaultScalar> {
+    gains: Vec<S>,
+    /// The factor by which the previous gains are multiplied on each update.
+    pub decay: S,
+}
+
+#[cfg(feature = "alloc")]
+impl<S> TrailBuffer<S>
+where
+    S: Scalar,
+{
+    /// Create a trail buffer for `len` speakers where all gains begin at `0.0`.
+    pub fn new(len: usize, decay: S) -> Self {
+        TrailBuffer {
+            gains: vec![S::from_f32(0.0); len],
+            decay,
+        }
+    }
+
+    /// The current gains for each speaker.
+    pub fn gains(&self) -> &[S] {
+        &self.gains
+    }
+
+    /// Decay the previous gains and take the maximum of each with the newly computed gains.
+    ///
+    /// **Panics** if `new_gains` is not the same length as the buffer.
+    pub fn update(&mut self, new_gains: &[S]) -> &[S] {
+        assert_eq!(self.gains.len(), new_gains.len());
+        for (g, &new) in self.gains.iter_mut().zip(new_gains) {
+            let decayed = *g * self.decay;
+            *g = if new > decayed { new } else { decayed };
+        }
+        &self.gains
+    }
+}
+
+/// Selects the `n` speakers nearest to the source, with hysteresis to prevent selections from
+/// flickering as a source hovers near the boundary of the selection.
+///
+/// A speaker that is already selected remains selected until its distance rank (where the nearest
+/// speaker has rank `0`) reaches `n + margin`. Any remaining slots are filled by the nearest
+/// unselected speakers.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub struct NearestSelector {
+    /// The number of speakers to select.
+    pub n: usize,
+    /// The number of ranks beyond `n` that a selected speaker may fall before being deselected.
+    pub margin: usize,
+    selected: Vec<usize>,
+}
+
+#[cfg(feature = "alloc")]
+impl NearestSelector {
+    /// Create a selector with no initial selection.
+    pub fn new(n: usize, margin: usize) -> Self {
+        NearestSelector {
+            n,
+            margin,
+            selected: vec![],
+        }
+    }
+
+    /// The indices of the currently selected speakers in ascending order.
+    pub fn selected(&self) -> &[usize] {
+        &self.selected
+    }
+
+    /// Update the selection for the given speakers, returning the indices of the selected
+    /// speakers in ascending order.
+    pub fn select<S>(&mut self, speakers: &[Speaker<S>]) -> &[usize]
+    where
+        S: Scalar,
+    {
+        let mut order: Vec<usize> = (0..speakers.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (speakers[a].distance, speakers[b].distance);
+            a.partial_cmp(&b).unwrap_or(core::cmp::Ordering::Equal)
+        });
+        let n = self.n.min(speakers.len());
+        let limit = n + self.margin;
+        let previous = core::mem::take(&mut self.selected);
+        let mut selected: Vec<usize> = order
+            .iter()
+            .take(limit)
+            .filter(|i| previous.contains(i))
+            .take(n)
+            .cloned()
+            .collect();
+        for &i in &order {
+            if selected.len() == n {
+                break;
+            }
+            if !selected.contains(&i) {
+                selected.push(i);
+            }
+        }
+        selected.sort_unstable();
+        self.selected = selected;
+        &self.selected
+    }
+}
+
+/// Follows the gain envelope of each speaker in order to automatically gate speakers that have
+/// been idle for a while, e.g. to reduce hiss from idle amplifiers in sparse installations.
+///
+/// Each speaker's envelope rises instantly with its gain and decays by the `release` factor on
+/// each update. An enabled speaker is gated once its envelope has been below the `threshold` for
+/// `hold` consecutive updates. A gated speaker is re-enabled once its gain has been at or above the
+/// `threshold` for `attack` consecutive updates.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub struct SpeakerGate<S = DefaultScalar> {
+    /// The gain below which a speaker is considered idle.
+    pub threshold: S,
+    /// The number of updates that a gated speaker's gain must reach the threshold before it is
+    /// re-enabled.
+    pub attack: usize,
+    /// The number of updates that an enabled speaker's envelope must remain below the threshold
+    /// before it is gated.
+    pub hold: usize,
+    /// The factor by which each envelope is multiplied on each update.
+    pub release: S,
+    envelopes: Vec<S>,
+    counts: Vec<usize>,
+    enabled: Vec<bool>,
+}
+
+#[cfg(feature = "alloc")]
+impl<S> SpeakerGate<S>
+where
+    S: Scalar,
+{
+    /// Create a gate for `len` speakers where all speakers begin enabled with an envelope of `0.0`.
+    pub fn new(len: usize, threshold: S, attack: usize, hold: usize, release: S) -> Self {
+        SpeakerGate {
+            threshold,
+            attack,
+            hold,
+            release,
+            envelopes: vec![S::from_f32(0.0); len],
+            counts: vec![0; len],
+            enabled: vec![true; len],
+        }
+    }
+
+    /// The current envelope of each speaker.
+    pub fn envelopes(&self) -> &[S] {
+        &self.envelopes
+    }
+
+    /// Whether or not each speaker is currently enabled.
+    pub fn enabled(&self) -> &[bool] {
+        &self.enabled
+    }
+
+    /// Follow the newly computed gains, returning whether or not each speaker is enabled.
+    ///
+    /// **Panics** if `gains` is not the same length as the gate.
+    pub fn update(&mut self, gains: &[S]) -> &[bool] {
+        assert_eq!(self.envelopes.len(), gains.len());
+        let states = self.envelopes.iter_mut().zip(&mut self.counts);
+        for ((&gain, (env, count)), enabled) in gains.iter().zip(states).zip(&mut self.enabled) {
+            let decayed = *env * self.release;
+            *env = if gain > decayed { gain } else { decayed };
+            // Count the consecutive updates in which the speaker would change state.
+            let changing = if *enabled {
+                *env < self.threshold
+            } else {
+                gain >= self.threshold
+            };
+            *count = if changing { *count + 1 } else { 0 };
+            let limit = if *enabled { self.hold } else { self.attack };
+            if changing && *count >= limit {
+                *enabled = !*enabled;
+                *count = 0;
+            }
+        }
+        &self.enabled
+    }
+}
+
+/// Automates the rolloff over time by linearly interpolating the `a` coefficient.
+///
+/// `a_coefficient` requires a `pow` which can be wasteful to compute for every frame. The ramp
+/// instead computes `a` once for each target rolloff and interpolates between the cached values.
+#[derive(Copy, Clone, Debug)]
+pub struct RolloffRamp<S = DefaultScalar> {
+    current: S,
+    target: S,
+    step: S,
+    remaining: usize,
+}
+
+impl<S> RolloffRamp<S>
+where
+    S: Scalar,
+{
+    /// Begin with a constant rolloff.
+    pub fn new(rolloff_db: S) -> Self {
+        let a = a_coefficient(rolloff_db);
+        RolloffRamp {
+            current: a,
+            target: a,
+            step: S::from_f32(0.0),
+            remaining: 0,
+        }
+    }
+
+    /// The current `a` coefficient.
+    pub fn a(&self) -> S {
+        self.current
+    }
+
+    /// Begin ramping towards the given rolloff over the given number of frames.
+    ///
+    /// If `frames` is `0` the target is applied immediately.
+    pub fn ramp_to(&mut self, rolloff_db: S, frames: usize) {
+        self.target = a_coefficient(rolloff_db);
+        self.remaining = frames;
+        if frames == 0 {
+            self.current = self.target;
+            self.step = S::from_f32(0.0);
+        } else {
+            self.step = (self.target - self.current) / S::from_f32(frames as f32);
+        }
+    }
+
+    /// Advance the ramp by a single frame, returning the new `a` coefficient.
+    ///
+    /// The final frame of a ramp always lands exactly on the target.
+    pub fn next_a(&mut self) -> S {
+        match self.remaining {
+            0 => (),
+            1 => {
+                self.current = self.target;
+                self.remaining = 0;
+            }
+            _ => {
+                self.current = self.current + self.step;
+                self.remaining -= 1;
+            }
+        }
+        self.current
+    }
+
+    /// Compute the gains for the given speakers with the current `a` coefficient.
+    ///
+    /// The gains are those of `modelled_gains` with a `RolloffModel::Constant` matching the
+    /// current rolloff, within which the rolloff shapes the gains. Call `next_a` once per frame to
+    /// advance the ramp.
+    ///
+    /// **Panics** if `out` is not the same length as `speakers`.
+    #[cfg(feature = "full")]
+    pub fn gains(&self, speakers: &[Speaker<S>], out: &mut [S])
+    where
+        S: Float,
+    {
+        let a = self.current;
+        attenuated_gains(speakers, |s| rolloff_attenuation(s.distance, a), out);
+    }
+}
+
+/// Nudge the source position towards the given attractors, each described by its position and a
+/// strength, such that a source gravitates towards points of interest before its gains are
+/// computed.
+///
+/// Each attractor pulls the source towards itself by a distance of `strength / d`, where `d` is
+/// the distance between the source and the attractor. The pull is capped at `d` such that the
+/// source never overshoots: a source within `sqrt(strength)` of an attractor is captured by it.
+/// The pulls of all attractors are evaluated from the original source position and summed. A
+/// strength of `0.0` has no effect.
+pub fn apply_attractors_2<S>(source: [S; 2], attractors: &[([S; 2], S)]) -> [S; 2]
+where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    let one = S::from_f32(1.0);
+    let mut adjusted = source;
+    for &(position, strength) in attractors {
+        let d2 = blurred_distance_2(source, position, zero);
+        if d2 == zero {
+            continue;
+        }
+        // Scaling the displacement by `strength / d²` yields a pull of `strength / d`.
+        let t = strength / d2;
+        let t = if t < one { t } else { one };
+        adjusted[0] = adjusted[0] + (position[0] - source[0]) * t;
+        adjusted[1] = adjusted[1] + (position[1] - source[1]) * t;
+    }
+    adjusted
+}
+
+/// Crossfade between the gains produced by two source positions using an equal-power law.
+///
+/// The gains for `pos_a` are weighted by `cos(t * π/2)` and the gains for `pos_b` by
+/// `sin(t * π/2)`, where `t` is in the range `0.0..=1.0`. Unlike crossfading two pre-computed gain
+/// fields, the gains for each position are recomputed from the speaker positions.
+///
+/// **Panics** if `speakers` is empty or if `out` is not the same length as `speakers`.
+#[cfg(all(feature = "full", feature = "alloc"))]
+pub fn blend_positions_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    pos_a: [S; 2],
+    pos_b: [S; 2],
+    t: S,
+    rolloff_db: S,
+    blur: S,
+    out: &mut [S],
+) where
+    S: Scalar + Float + FloatConst,
+{
+    assert_eq!(speakers.len(), out.len());
+    let spkrs_a = speakers_2(speakers, pos_a, blur);
+    let spkrs_b = speakers_2(speakers, pos_b, blur);
+    let gains_a = SpeakerGains::new(&spkrs_a, rolloff_db);
+    let gains_b = SpeakerGains::new(&spkrs_b, rolloff_db);
+    let theta = t * S::FRAC_PI_2();
+    let (amp_a, amp_b) = (theta.cos(), theta.sin());
+    for ((o, a), b) in out.iter_mut().zip(gains_a).zip(gains_b) {
+        *o = a * amp_a + b * amp_b;
+    }
+}
+
+/// Compute the gains for a source at the given position, where the weight of each speaker is first
+/// multiplied by the result of `occlusion(source, speaker_index)`.
+///
+/// This allows for modelling position-dependent attenuation (e.g. a speaker partially blocked from
+/// certain regions of the space) without modifying the core algorithm. An occlusion of `0.0` is
+/// equivalent to muting the speaker, while `1.0` leaves it unchanged.
+///
+/// **Panics** if `speakers` is empty or if `out` is not the same length as `speakers`.
+#[cfg(feature = "alloc")]
+pub fn occluded_gains_2<S, F>(
+    speakers: &[PositionedSpeaker<S>],
+    source: [S; 2],
+    rolloff_db: S,
+    blur: S,
+    occlusion: F,
+    out: &mut [S],
+) where
+    S: Scalar,
+    F: Fn([S; 2], usize) -> S,
+{
+    assert_eq!(speakers.len(), out.len());
+    let mut spkrs = speakers_2(speakers, source, blur);
+    for (i, s) in spkrs.iter_mut().enumerate() {
+        s.weight = s.weight * occlusion(source, i);
+    }
+    for (o, g) in out.iter_mut().zip(SpeakerGains::new(&spkrs, rolloff_db)) {
+        *o = g;
+    }
+}
+
+/// Compute the gains for a directional source facing in the direction `facing`, such that speakers
+/// behind the source receive less of its energy.
+///
+/// Each speaker's weight is multiplied by `(0.5 * (1 + cos θ))^pattern`, where `θ` is the angle
+/// between `facing` and the direction from the source to the speaker. A `pattern` of `0.0` is
+/// omnidirectional, `1.0` is a cardioid and larger values narrow the pattern further. A speaker
+/// directly behind the source receives a gain of `0.0` for any positive `pattern`.
+///
+/// **Panics** if `speakers` is empty or if `out` is not the same length as `speakers`.
+#[cfg(feature = "alloc")]
+pub fn directional_source_gains_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    source: [S; 2],
+    facing: [S; 2],
+    pattern: S,
+    rolloff_db: S,
+    blur: S,
+    out: &mut [S],
+) where
+    S: Scalar,
+{
+    // The source radiates like a directive speaker, so the directivity is evaluated towards each
+    // speaker in turn.
+    let radiator = DirectiveSpeaker {
+        position: source,
+        facing,
+        weight: S::from_f32(1.0),
+    };
+    let occlusion = |_, i: usize| {
+        let directivity = radiator.directivity(speakers[i].position);
+        directivity.pow(pattern)
+    };
+    occluded_gains_2(speakers, source, rolloff_db, blur, occlusion, out);
+}
+
+/// Compute the gains for the given speakers, treating any speaker further than `max_distance` from
+/// the source as disabled.
+///
+/// Disabled speakers receive a gain of `0.0` and are excluded from the `k` coefficient. If every
+/// speaker lies beyond `max_distance`, the nearest speaker (or speakers, if equidistant) remain
+/// enabled so that the source never falls silent.
+///
+/// **Panics** if `speakers` is empty or if `out` is not the same length as `speakers`.
+#[cfg(feature = "alloc")]
+pub fn culled_gains<S>(speakers: &[Speaker<S>], rolloff_db: S, max_distance: S, out: &mut [S])
+where
+    S: Scalar,
+{
+    assert_eq!(speakers.len(), out.len());
+    let nearest = speakers
+        .iter()
+        .map(|s| s.distance)
+        .fold(speakers[0].distance, |min, d| if d < min { d } else { min });
+    let max_distance = if nearest < max_distance {
+        max_distance
+    } else {
+        nearest
+    };
+    let zero = S::from_f32(0.0);
+    let spkrs: Vec<_> = speakers
+        .iter()
+        .map(|s| match s.distance > max_distance {
+            true => Speaker { weight: zero, ..*s },
+            false => *s,
+        })
+        .collect();
+    for (o, g) in out.iter_mut().zip(SpeakerGains::new(&spkrs, rolloff_db)) {
+        *o = g;
+    }
+}
+
+/// A taper applied to a speaker's weight by its distance from the source, such that the speaker
+/// only engages while the source is nearby.
+///
+/// Unlike the hard cutoff of `culled_gains`, the weight ramps smoothly from its nominal value to
+/// `0.0` as the source moves away.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WeightRamp<S = DefaultScalar> {
+    /// The distance within which the speaker is fully engaged.
+    pub engage_distance: S,
+    /// The distance beyond `engage_distance` over which the weight ramps down to `0.0`.
+    pub ramp_width: S,
+}
+
+impl<S> WeightRamp<S>
+where
+    S: Scalar,
+{
+    /// The factor in the range `0.0..=1.0` by which the weight of a speaker at the given distance
+    /// is scaled.
+    ///
+    /// The factor is `1.0` up to `engage_distance`, `0.0` from `engage_distance + ramp_width` and
+    /// linearly interpolated in between.
+    pub fn factor(&self, distance: S) -> S {
+        let zero = S::from_f32(0.0);
+        let one = S::from_f32(1.0);
+        if distance <= self.engage_distance {
+            one
+        } else if distance >= self.engage_distance + self.ramp_width {
+            zero
+        } else {
+            one - (distance - self.engage_distance) / self.ramp_width
+        }
+    }
+
+    /// The given speaker with the ramp applied to its weight.
+    pub fn apply(&self, speaker: Speaker<S>) -> Speaker<S> {
+        let weight = speaker.weight * self.factor(speaker.distance);
+        Speaker { weight, ..speaker }
+    }
+}
+
+/// Compute the gains for the given speakers with each speaker's `WeightRamp` first applied to its
+/// weight.
+///
+/// If every speaker is fully disengaged, all gains are `0.0`.
+///
+/// **Panics** if `speakers` is empty or if `ramps` and `out` are not the same length as
+/// `speakers`.
+#[cfg(feature = "alloc")]
+pub fn ramped_gains<S>(
+    speakers: &[Speaker<S>],
+    ramps: &[WeightRamp<S>],
+    rolloff_db: S,
+    out: &mut [S],
+) where
+    S: Scalar,
+{
+    assert_eq!(speakers.len(), ramps.len());
+    assert_eq!(speakers.len(), out.len());
+    let spkrs: Vec<_> = speakers
+        .iter()
+        .zip(ramps)
+        .map(|(&s, ramp)| ramp.apply(s))
+        .collect();
+    for (o, g) in out.iter_mut().zip(SpeakerGains::new(&spkrs, rolloff_db)) {
+        *o = g;
+    }
+}
+
+/// A curve remapping the normalised distance of each speaker prior to the gain computation,
+/// allowing for artistic control over the relationship between distance and gain.
+///
+/// Implemented for all `Fn(S) -> S`.
+pub trait ResponseCurve<S> {
+    /// Map the given normalised distance in the range `0.0..=1.0` to a new normalised distance.
+    fn map(&self, normalized_distance: S) -> S;
+
+    /// Remap the given distance, normalised by dividing by `max_distance`.
+    ///
+    /// The default implementation maps `distance / max_distance` and scales the result back up by
+    /// `max_distance`.
+    fn apply(&self, distance: S, max_distance: S) -> S
+    where
+        S: Scalar,
+    {
+        self.map(distance / max_distance) * max_distance
+    }
+}
+
+impl<S, F> ResponseCurve<S> for F
+where
+    F: Fn(S) -> S,
+{
+    fn map(&self, normalized_distance: S) -> S {
+        (*self)(normalized_distance)
+    }
+}
+
+/// The identity curve, leaving every distance exactly as it is.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Identity;
+
+impl<S> ResponseCurve<S> for Identity {
+    fn map(&self, normalized_distance: S) -> S {
+        normalized_distance
+    }
+
+    fn apply(&self, distance: S, _max_distance: S) -> S
+    where
+        S: Scalar,
+    {
+        distance
+    }
+}
+
+/// A cubic bezier curve from `(0, 0)` to `(1, 1)` whose output at `t` is
+/// `3(1 - t)²t * c1 + 3(1 - t)t² * c2 + t³`.
+///
+/// Control values of `1/3` and `2/3` produce the identity. Lower control values steepen the growth
+/// of distance towards the far end of the range, sharpening localisation, while higher values
+/// flatten it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CubicBezier<S = DefaultScalar> {
+    /// The first control value.
+    pub c1: S,
+    /// The second control value.
+    pub c2: S,
+}
+
+impl<S> ResponseCurve<S> for CubicBezier<S>
+where
+    S: Scalar,
+{
+    fn map(&self, t: S) -> S {
+        let one = S::from_f32(1.0);
+        let three = S::from_f32(3.0);
+        let u = one - t;
+        three * u * u * t * self.c1 + three * u * t * t * self.c2 + t * t * t
+    }
+}
+
+/// Compute the gains for the given speakers with each distance first remapped by the given curve.
+///
+/// Distances are normalised by the greatest distance prior to mapping, such that the curve always
+/// operates over the range `0.0..=1.0` (see `ResponseCurve::apply`). The `Identity` curve produces
+/// the same gains as `SpeakerGains::new`.
+///
+/// **Panics** if `speakers` is empty or if `out` is not the same length as `speakers`.
+#[cfg(feature = "alloc")]
+pub fn curved_gains<S, C>(speakers: &[Speaker<S>], curve: &C, rolloff_db: S, out: &mut [S])
+where
+    S: Scalar,
+    C: ResponseCurve<S> + ?Sized,
+{
+    assert_eq!(speakers.len(), out.len());
+    let max = speakers
+        .iter()
+        .fold(speakers[0].distance, |max, s| match s.distance > max {
+            true => s.distance,
+            false => max,
+        });
+    let spkrs: Vec<_> = speakers
+        .iter()
+        .map(|s| Speaker {
+            distance: curve.apply(s.distance, max),
+            ..*s
+        })
+        .collect();
+    for (o, g) in out.iter_mut().zip(SpeakerGains::new(&spkrs, rolloff_db)) {
+        *o = g;
+    }
+}
+
+/// Approximate the gains for a line source spanning from `start` to `end`.
+///
+/// The segment is sampled at `samples` evenly spaced points including both ends (or its midpoint
+/// if `samples` is `1`), and the resulting gains for each point are averaged.
+///
+/// **Panics** if `speakers` is empty, if `samples` is `0` or if `out` is not the same length as
+/// `speakers`.
+#[cfg(feature = "alloc")]
+pub fn line_source_gains_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    start: [S; 2],
+    end: [S; 2],
+    samples: usize,
+    rolloff_db: S,
+    blur: S,
+    out: &mut [S],
+) where
+    S: Scalar,
+{
+    assert!(samples > 0);
+    assert_eq!(speakers.len(), out.len());
+    let zero = S::from_f32(0.0);
+    for o in out.iter_mut() {
+        *o = zero;
+    }
+    let n = S::from_f32(samples as f32);
+    for i in 0..samples {
+        let t = match samples {
+            1 => S::from_f32(0.5),
+            _ => S::from_f32(i as f32) / S::from_f32((samples - 1) as f32),
+        };
+        let point = [
+            start[0] + (end[0] - start[0]) * t,
+            start[1] + (end[1] - start[1]) * t,
+        ];
+        let spkrs = speakers_2(speakers, point, blur);
+        for (o, g) in out.iter_mut().zip(SpeakerGains::new(&spkrs, rolloff_db)) {
+            *o = *o + g / n;
+        }
+    }
+}
+
+/// The gain of the speaker at `query_position` for a source at the given position, or `None` if no
+/// speaker lies within `tolerance` of `query_position`.
+///
+/// If multiple speakers lie within `tolerance`, the nearest to `query_position` is chosen. This
+/// allows for inspecting a speaker's gain by its physical position, e.g. within a UI, without
+/// knowing its index.
+#[cfg(feature = "alloc")]
+pub fn gain_at_position_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    source: [S; 2],
+    query_position: [S; 2],
+    tolerance: S,
+    rolloff_db: S,
+    blur: S,
+) -> Option<S>
+where
+    S: Scalar,
+{
+    if speakers.is_empty() {
+        return None;
+    }
+    let zero = S::from_f32(0.0);
+    let i = nearest_index_2(speakers, query_position);
+    if blurred_distance_2(query_position, speakers[i].position, zero) > tolerance * tolerance {
+        return None;
+    }
+    let spkrs = speakers_2(speakers, source, blur);
+    SpeakerGains::new(&spkrs, rolloff_db).nth(i)
+}
+
+/// Snap the source to the single speaker nearest to it, bypassing the DBAP calculation entirely.
+///
+/// The nearest speaker receives a gain of `1.0` while all others receive `0.0`. If multiple
+/// speakers are equally near, the first is chosen.
+///
+/// **Panics** if `speakers` is empty or if `out` is not the same length as `speakers`.
+pub fn nearest_speaker_gains_2<S>(speakers: &[PositionedSpeaker<S>], source: [S; 2], out: &mut [S])
+where
+    S: Scalar,
+{
+    assert_eq!(speakers.len(), out.len());
+    let nearest = nearest_index_2(speakers, source);
+    for o in out.iter_mut() {
+        *o = S::from_f32(0.0);
+    }
+    out[nearest] = S::from_f32(1.0);
+}
+
+/// Pan the source between the two speakers nearest to it using an equal-power law, in the style of
+/// pairwise panning approaches such as VBAP.
+///
+/// For the nearest pair at distances `d₁` and `d₂`, the gains are `sqrt(d₂ / (d₁ + d₂))` and
+/// `sqrt(d₁ / (d₁ + d₂))` respectively, such that their total power is always `1.0`. All other
+/// speakers receive `0.0`. A lone speaker receives a gain of `1.0`.
+///
+/// **Panics** if `speakers` is empty or if `out` is not the same length as `speakers`.
+#[cfg(feature = "alloc")]
+pub fn nearest_pair_gains_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    source: [S; 2],
+    blur: S,
+    out: &mut [S],
+) where
+    S: Scalar,
+{
+    assert_eq!(speakers.len(), out.len());
+    let zero = S::from_f32(0.0);
+    let half = S::from_f32(0.5);
+    let spkrs = speakers_2(speakers, source, blur);
+    for o in out.iter_mut() {
+        *o = zero;
+    }
+    let nearest = nearest_index_2(speakers, source);
+    let second = (0..spkrs.len())
+        .filter(|&i| i != nearest)
+        .fold(None, |min: Option<usize>, i| match min {
+            Some(m) if spkrs[m].distance <= spkrs[i].distance => Some(m),
+            _ => Some(i),
+        });
+    let second = match second {
+        None => {
+            out[nearest] = S::from_f32(1.0);
+            return;
+        }
+        Some(i) => i,
+    };
+    let (d1, d2) = (spkrs[nearest].distance, spkrs[second].distance);
+    let total = d1 + d2;
+    let (p1, p2) = match total == zero {
+        true => (half, half),
+        false => (d2 / total, d1 / total),
+    };
+    out[nearest] = p1.pow(half);
+    out[second] = p2.pow(half);
+}
+
+/// Crossfade between nearest-pair panning (see `nearest_pair_gains_2`) and DBAP across all
+/// speakers.
+///
+/// A `blend` of `0.0` produces purely nearest-pair gains while `1.0` produces purely DBAP gains,
+/// with the two linearly interpolated in between.
+///
+/// **Panics** if `speakers` is empty or if `out` is not the same length as `speakers`.
+#[cfg(feature = "alloc")]
+pub fn blend_dbap_vbap_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    source: [S; 2],
+    blend: S,
+    rolloff_db: S,
+    blur: S,
+    out: &mut [S],
+) where
+    S: Scalar,
+{
+    nearest_pair_gains_2(speakers, source, blur, out);
+    let one = S::from_f32(1.0);
+    let spkrs = speakers_2(speakers, source, blur);
+    for (o, g) in out.iter_mut().zip(SpeakerGains::new(&spkrs, rolloff_db)) {
+        *o = *o * (one - blend) + g * blend;
+    }
+}
+
+/// Compute gains from flat buffers of speaker distances and weights.
+///
+/// This is equivalent to `SpeakerGains::new` but has no generics and no structs within its
+/// signature, simplifying FFI and `wasm-bindgen` wrapping.
+///
+/// **Panics** if `distances` is empty or if `weights` and `out` are not the same length as
+/// `distances`.
+pub fn flat_gains_f32(distances: &[f32], weights: &[f32], rolloff_db: f32, out: &mut [f32]) {
+    flat_gains_generic(distances, weights, rolloff_db, out)
+}
+
+/// Compute gains from flat buffers of *squared* speaker distances and weights, as produced by
+/// `blurred_distance_2`, avoiding the square root required to construct each `Speaker`.
+///
+/// The distance only ever appears squared within the DBAP formulae: the `k` coefficient sums
+/// `wᵢ² / dᵢ²` and each gain `k·wᵢ / (2a·dᵢ) / dᵢ` simplifies to `k·wᵢ / (2a·dᵢ²)`. No square root
+/// is taken at all. The linear distance is only required by features beyond the gains themselves,
+/// such as `SpeakerGains::with_delays`.
+///
+/// Speakers with a squared distance of `0.0` are excluded from `k`, as in `SpeakerGains`.
+///
+/// **Panics** if `squared_distances` is empty or if `weights` and `out` are not the same length as
+/// `squared_distances`.
+pub fn squared_distance_gains<S>(
+    squared_distances: &[S],
+    weights: &[S],
+    rolloff_db: S,
+    out: &mut [S],
+) where
+    S: Scalar,
+{
+    assert!(!squared_distances.is_empty());
+    assert_eq!(squared_distances.len(), weights.len());
+    assert_eq!(squared_distances.len(), out.len());
+    let zero = S::from_f32(0.0);
+    let a = a_coefficient(rolloff_db);
+    let sum = squared_distances
+        .iter()
+        .zip(weights)
+        .filter(|&(&d2, _)| d2 != zero)
+        .map(|(&d2, &w)| w * w / d2)
+        .sum();
+    let k = k_from_sums(a, mean_weight(weights.iter().cloned()), sum);
+    for ((o, &d2), &w) in out.iter_mut().zip(squared_distances).zip(weights) {
+        *o = k * w / ((a + a) * d2);
+    }
+}
+
+// The generic implementation of `flat_gains_f32`.
+fn flat_gains_generic<S>(distances: &[S], weights: &[S], rolloff_db: S, out: &mut [S])
+where
+    S: Scalar,
+{
+    assert!(!distances.is_empty());
+    assert_eq!(distances.len(), weights.len());
+    assert_eq!(distances.len(), out.len());
+    let speaker = |i| Speaker::new(distances[i], weights[i]);
+    let a = a_coefficient(rolloff_db);
+    let (mean_weight, sum) = k_sums((0..distances.len()).map(speaker));
+    let k = k_from_sums(a, mean_weight, sum);
+    for (i, o) in out.iter_mut().enumerate() {
+        let s = speaker(i);
+        *o = v_speaker_relative_amplitude(&s, k, a) / s.distance;
+    }
+}
+
+/// A C API for calling into the crate from other languages, enabled via the `capi` feature.
+///
+/// The C header `include/dbap.h` is generated from this module via
+/// `cbindgen --config cbindgen.toml --output include/dbap.h`. A static or dynamic library may be
+/// built via `cargo rustc --release --features capi --crate-type staticlib` (or `cdylib`).
+///
+/// Every function validates its arguments before reading from or writing to any buffer, returning
+/// a `DbapStatus` describing the result. No function panics across the FFI boundary.
+#[cfg(feature = "capi")]
+pub mod capi {
+    use core::slice;
+
+    /// The result of a call into the C API.
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum DbapStatus {
+        /// The call succeeded and the output buffer was written.
+        DbapOk = 0,
+        /// One of the given pointers was null. The output buffer was not written.
+        DbapNullPointer = 1,
+        /// The given length was `0`. The output buffer was not written.
+        DbapInvalidLength = 2,
+    }
+
+    /// Compute the gain of each of `len` speakers from their distances and weights, writing the
+    /// results to `out`. See `flat_gains_f32`.
+    ///
+    /// # Safety
+    ///
+    /// Unless null, `distances`, `weights` and `out` must each point to `len` valid, properly
+    /// aligned `f32`s, and `out` must not overlap `distances` or `weights`.
+    #[no_mangle]
+    pub unsafe extern "C" fn dbap_compute_gains_f32(
+        distances: *const f32,
+        weights: *const f32,
+        len: usize,
+        rolloff_db: f32,
+        out: *mut f32,
+    ) -> DbapStatus {
+        compute_gains(distances, weights, len, rolloff_db, out)
+    }
+
+    /// The same as `dbap_compute_gains_f32`, but for `f64`s.
+    ///
+    /// # Safety
+    ///
+    /// Unless null, `distances`, `weights` and `out` must each point to `len` valid, properly
+    /// aligned `f64`s, and `out` must not overlap `distances` or `weights`.
+    #[no_mangle]
+    pub unsafe extern "C" fn dbap_compute_gains_f64(
+        distances: *const f64,
+        weights: *const f64,
+        len: usize,
+        rolloff_db: f64,
+        out: *mut f64,
+    ) -> DbapStatus {
+        compute_gains(distances, weights, len, rolloff_db, out)
+    }
+
+    // Validate the arguments and compute the gains. See `dbap_compute_gains_f32` for safety.
+    unsafe fn compute_gains<S>(
+        distances: *const S,
+        weights: *const S,
+        len: usize,
+        rolloff_db: S,
+        out: *mut S,
+    ) -> DbapStatus
+    where
+        S: super::Scalar,
+    {
+        if distances.is_null() || weights.is_null() || out.is_null() {
+            return DbapStatus::DbapNullPointer;
+        }
+        if len == 0 {
+            return DbapStatus::DbapInvalidLength;
+        }
+        let distances = slice::from_raw_parts(distances, len);
+        let weights = slice::from_raw_parts(weights, len);
+        let out = slice::from_raw_parts_mut(out, len);
+        super::flat_gains_generic(distances, weights, rolloff_db, out);
+        DbapStatus::DbapOk
+    }
+}
+
+/// The axis-aligned bounding box of the speaker positions as a `(min, max)` pair of corners.
+///
+/// **Panics** if `speakers` is empty.
+pub fn bounding_box_2<S>(speakers: &[PositionedSpeaker<S>]) -> ([S; 2], [S; 2])
+where
+    S: Scalar,
+{
+    let first = speakers[0].position;
+    speakers.iter().fold((first, first), |(min, max), s| {
+        let [x, y] = s.position;
+        let lo = |a: S, b: S| if b < a { b } else { a };
+        let hi = |a: S, b: S| if b > a { b } else { a };
+        (
+            [lo(min[0], x), lo(min[1], y)],
+            [hi(max[0], x), hi(max[1], y)],
+        )
+    })
+}
+
+/// A suggested starting point for the `blur` amount given the layout of the speakers.
+///
+/// The heuristic returns 1% of the length of the diagonal of the layout's bounding box, such that
+/// the suggestion scales with the units used to describe the layout.
+///
+/// **Panics** if `speakers` is empty.
+pub fn suggested_blur<S>(speakers: &[PositionedSpeaker<S>]) -> S
+where
+    S: Scalar,
+{
+    let (min, max) = bounding_box_2(speakers);
+    let diagonal = blurred_distance_2(min, max, S::from_f32(0.0)).pow(S::from_f32(0.5));
+    diagonal / S::from_f32(100.0)
+}
+
+/// A measure of how well-posed the layout of the speakers is for panning in two dimensions.
+///
+/// Returns the condition number `λmax / λmin` of the covariance of the speaker positions about
+/// their centroid. A value of `1.0` indicates speakers spread evenly in all directions, while
+/// larger values indicate a layout that is stretched along one axis. Nearly collinear layouts
+/// approach infinity, and are ill-conditioned in that small movements of a source away from the
+/// line cause large swings in gain. Thresholding the result allows for warning users of a poorly
+/// posed layout. If all speakers share the same position the result is `NaN`.
+///
+/// **Panics** if `speakers` is empty.
+pub fn layout_conditioning_2<S>(speakers: &[PositionedSpeaker<S>]) -> S
+where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    let n = S::from_f32(speakers.len() as f32);
+    let (sx, sy) = speakers.iter().fold((zero, zero), |(x, y), s| {
+        (x + s.position[0], y + s.position[1])
+    });
+    let (cx, cy) = (sx / n, sy / n);
+    let (xx, xy, yy) = speakers.iter().fold((zero, zero, zero), |(xx, xy, yy), s| {
+        let (dx, dy) = (s.position[0] - cx, s.position[1] - cy);
+        (xx + dx * dx, xy + dx * dy, yy + dy * dy)
+    });
+    let half_trace = (xx + yy) / S::from_f32(2.0);
+    let det = xx * yy - xy * xy;
+    let disc = half_trace * half_trace - det;
+    let root = match disc > zero {
+        true => disc.pow(S::from_f32(0.5)),
+        false => zero,
+    };
+    (half_trace + root) / (half_trace - root)
+}
+
+/// Compute the gains for a source where each speaker is surrounded by a "dead zone" within which
+/// the source snaps entirely to that speaker.
+///
+/// Where the source lies within `snap_radius` of its nearest speaker, the nearest speaker receives
+/// a gain of `1.0` and all others `0.0`. Beyond `snap_radius + transition` the regular DBAP gains
+/// are produced. Within the transition band the snapped and DBAP gains are linearly crossfaded by
+/// the source's distance through the band.
+///
+/// **Panics** if `speakers` is empty or if `out` is not the same length as `speakers`.
+#[cfg(feature = "alloc")]
+pub fn dead_zone_gains_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    source: [S; 2],
+    rolloff_db: S,
+    blur: S,
+    snap_radius: S,
+    transition: S,
+    out: &mut [S],
+) where
+    S: Scalar,
+{
+    nearest_speaker_gains_2(speakers, source, out);
+    let zero = S::from_f32(0.0);
+    let one = S::from_f32(1.0);
+    let nearest = nearest_index_2(speakers, source);
+    let distance =
+        blurred_distance_2(source, speakers[nearest].position, zero).pow(S::from_f32(0.5));
+    let mix = if distance <= snap_radius {
+        zero
+    } else if distance >= snap_radius + transition {
+        one
+    } else {
+        (distance - snap_radius) / transition
+    };
+    if mix == zero {
+        return;
+    }
+    let spkrs = speakers_2(speakers, source, blur);
+    for (o, g) in out.iter_mut().zip(SpeakerGains::new(&spkrs, rolloff_db)) {
+        *o = *o * (one - mix) + g * mix;
+    }
+}
+
+/// Two speakers (e.g. a left and right pair) linked to act as a single spatial entity with a shared
+/// weight, while still producing two outputs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpeakerPair<S = DefaultScalar> {
+    /// The index of the first member of the pair.
+    pub left: usize,
+    /// The index of the second member of the pair.
+    pub right: usize,
+    /// The weight shared by both members, in place of their individual weights.
+    pub weight: S,
+}
+
+/// How a linked `SpeakerPair` is counted within the `k` coefficient.
+///
+/// Both settings distribute the gains in the same relative proportions, differing only by a
+/// common scale factor.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PairCounting {
+    /// The pair contributes to `k` once, as a single entity whose gain is reproduced by both
+    /// members. The invariant described by `dbap_energy` holds across the entities rather than the
+    /// outputs, such that the field as a whole is louder than with `Members`.
+    #[default]
+    Entity,
+    /// The pair contributes to `k` once per member, as if each member were an independent speaker
+    /// positioned at the centre of the pair. The invariant described by `dbap_energy` holds across
+    /// the outputs.
+    Members,
+}
+
+/// Compute the gains for a source where each of the given pairs of speakers act as a single
+/// spatial entity.
+///
+/// Each pair is positioned at the midpoint of its two members with the pair's shared weight, and
+/// both members receive the gain of that entity. The pair is counted towards the `k` coefficient
+/// as described by `counting`. Speakers belonging to no pair are treated as usual.
+///
+/// **Panics** if `speakers` is empty, if a pair refers to a speaker index that is out of range or
+/// that already belongs to another pair, or if `out` is not the same length as `speakers`.
+#[cfg(feature = "alloc")]
+pub fn paired_gains_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    pairs: &[SpeakerPair<S>],
+    counting: PairCounting,
+    source: [S; 2],
+    rolloff_db: S,
+    blur: S,
+    out: &mut [S],
+) where
+    S: Scalar,
+{
+    assert_eq!(speakers.len(), out.len());
+    let half = S::from_f32(0.5);
+    let mut paired = vec![false; speakers.len()];
+    for pair in pairs {
+        for &i in &[pair.left, pair.right] {
+            assert!(!paired[i], "speaker {} belongs to more than one pair", i);
+            paired[i] = true;
+        }
+    }
+    // Each entity alongside the indices of the speakers that receive its gain.
+    let mut entities: Vec<(Speaker<S>, &[usize])> = Vec::with_capacity(speakers.len());
+    let singles: Vec<usize> = (0..speakers.len()).collect();
+    for (i, s) in speakers.iter().enumerate().filter(|&(i, _)| !paired[i]) {
+        entities.push((s.speaker(source, blur), &singles[i..i + 1]));
+    }
+    let members: Vec<[usize; 2]> = pairs.iter().map(|p| [p.left, p.right]).collect();
+    for (pair, members) in pairs.iter().zip(&members) {
+        let [l, r] = [speakers[pair.left].position, speakers[pair.right].position];
+        let centre = PositionedSpeaker {
+            position: [(l[0] + r[0]) * half, (l[1] + r[1]) * half],
+            weight: pair.weight,
+        };
+        let speaker = centre.speaker(source, blur);
+        match counting {
+            PairCounting::Entity => entities.push((speaker, &members[..])),
+            PairCounting::Members => {
+                entities.push((speaker, &members[..1]));
+                entities.push((speaker, &members[1..]));
+            }
+        }
+    }
+    let spkrs: Vec<_> = entities.iter().map(|&(s, _)| s).collect();
+    for ((_, indices), g) in entities.iter().zip(SpeakerGains::new(&spkrs, rolloff_db)) {
+        for &i in indices.iter() {
+            out[i] = g;
+        }
+    }
+}
+
+/// A rectangular region of the space driven by its own subset of speakers.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Zone<S = DefaultScalar> {
+    /// The minimum corner of the region.
+    pub min: [S; 2],
+    /// The maximum corner of the region.
+    pub max: [S; 2],
+    /// The indices of the speakers that belong to the zone.
+    pub speakers: Vec<usize>,
+}
+
+#[cfg(feature = "alloc")]
+impl<S> Zone<S>
+where
+    S: Scalar,
+{
+    /// The distance from the given point to the nearest edge of the region, or `0.0` if the point
+    /// lies within it.
+    pub fn distance(&self, point: [S; 2]) -> S {
+        let zero = S::from_f32(0.0);
+        let outside = |p: S, min: S, max: S| match (p < min, p > max) {
+            (true, _) => min - p,
+            (_, true) => p - max,
+            _ => zero,
+        };
+        let dx = outside(point[0], self.min[0], self.max[0]);
+        let dy = outside(point[1], self.min[1], self.max[1]);
+        (dx * dx + dy * dy).pow(S::from_f32(0.5))
+    }
+}
+
+/// A partitioning of the space into zones, each driven by its own subset of speakers.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Zones<S = DefaultScalar> {
+    /// The zones, which may overlap or share speakers.
+    pub zones: Vec<Zone<S>>,
+    /// The distance beyond the edge of a zone over which it fades out.
+    pub crossfade: S,
+}
+
+#[cfg(feature = "alloc")]
+impl<S> Zones<S>
+where
+    S: Scalar,
+{
+    /// The amount by which each zone is active for a source at the given point, summing to `1.0`.
+    ///
+    /// A zone is fully active for a source within it and fades out linearly as the source moves up
+    /// to `crossfade` beyond its edge, after which the activations are normalised. If the source
+    /// lies beyond the crossfade of every zone, the nearest zone is fully active.
+    ///
+    /// **Panics** if there are no zones.
+    pub fn activations(&self, point: [S; 2]) -> Vec<S> {
+        let zero = S::from_f32(0.0);
+        let one = S::from_f32(1.0);
+        let distances: Vec<S> = self.zones.iter().map(|z| z.distance(point)).collect();
+        let mut activations: Vec<S> = distances
+            .iter()
+            .map(|&d| match d == zero {
+                true => one,
+                false if d >= self.crossfade => zero,
+                false => one - d / self.crossfade,
+            })
+            .collect();
+        let sum: S = activations.iter().cloned().sum();
+        if sum == zero {
+            let nearest =
+                (1..distances.len()).fold(0, |min, i| match distances[i] < distances[min] {
+                    true => i,
+                    false => min,
+                });
+            activations[nearest] = one;
+            return activations;
+        }
+        for a in activations.iter_mut() {
+            *a = *a / sum;
+        }
+        activations
+    }
+}
+
+/// Compute the gains for a source where only the speakers of the zones in which it lies receive
+/// gain.
+///
+/// DBAP is run over the speakers of each active zone in isolation, and the resulting gains are
+/// mixed by each zone's activation as described by `Zones::activations`, crossfading between
+/// zones as the source crosses their boundaries. Speakers belonging to no active zone receive a
+/// gain of `0.0`. A zone without any speakers is silent, such that its share of the activation is
+/// not redistributed to the other zones.
+///
+/// **Panics** if `speakers` is empty, if there are no zones, if a zone refers to a speaker index
+/// that is out of range or if `out` is not the same length as `speakers`.
+#[cfg(feature = "alloc")]
+pub fn zone_gains_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    zones: &Zones<S>,
+    source: [S; 2],
+    rolloff_db: S,
+    blur: S,
+    out: &mut [S],
+) where
+    S: Scalar,
+{
+    assert_eq!(speakers.len(), out.len());
+    let zero = S::from_f32(0.0);
+    for o in out.iter_mut() {
+        *o = zero;
+    }
+    let all = speakers_2(speakers, source, blur);
+    let activations = zones.activations(source);
+    for (zone, &activation) in zones.zones.iter().zip(&activations) {
+        if activation == zero || zone.speakers.is_empty() {
+            continue;
+        }
+        let spkrs: Vec<_> = zone.speakers.iter().map(|&i| all[i]).collect();
+        for (&i, g) in zone
+            .speakers
+            .iter()
+            .zip(SpeakerGains::new(&spkrs, rolloff_db))
+        {
+            out[i] = out[i] + g * activation;
+        }
+    }
+}
+
+/// Collapse near-equal gains to exactly equal values, stabilising the image where many speakers are
+/// nearly equidistant from the source and tiny numerical differences would otherwise decide it.
+///
+/// The gains are sorted and grouped such that each gain within a group lies within `tolerance` of
+/// the next greatest gain within the same group. Note that groups may therefore span more than
+/// `tolerance` in total when near-equal gains form a chain. Each gain is replaced by the mean of
+/// its group, preserving the sum of the gains. A `tolerance` of `0.0` leaves the gains unchanged.
+#[cfg(feature = "alloc")]
+pub fn stabilize_gains<S>(gains: &mut [S], tolerance: S)
+where
+    S: Scalar,
+{
+    let mut order: Vec<usize> = (0..gains.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (gains[a], gains[b]);
+        a.partial_cmp(&b).unwrap_or(core::cmp::Ordering::Equal)
+    });
+    let mut start = 0;
+    for end in 1..=order.len() {
+        let split = end == order.len() || gains[order[end]] - gains[order[end - 1]] > tolerance;
+        if !split {
+            continue;
+        }
+        let group = &order[start..end];
+        let sum: S = group.iter().map(|&i| gains[i]).sum();
+        let mean = mean(sum, group.len());
+        for &i in group {
+            gains[i] = mean;
+        }
+        start = end;
+    }
+}
+
+/// Snap each gain to the nearest point on a grid of `step_db` decibel steps, e.g. to match the
+/// discrete steps of a physical fader.
+///
+/// Quantisation occurs in the dB domain relative to unity gain (`0 dB`), so unity always lies on
+/// the grid. Gains of `0.0` (i.e. `-inf dB`) are left at `0.0`.
+#[cfg(feature = "full")]
+pub fn quantize_gains<S>(gains: &mut [S], step_db: S)
+where
+    S: Scalar + Float,
+{
+    let twenty = S::from_f32(20.0);
+    let ten = S::from_f32(10.0);
+    for g in gains {
+        if *g == S::zero() {
+            continue;
+        }
+        let db = twenty * g.log10();
+        let snapped = (db / step_db).round() * step_db;
+        *g = ten.powf(snapped / twenty);
+    }
+}
+
+/// Map a gain to an intensity in the range `0.0..=1.0`, e.g. for driving the brightness of a
+/// speaker within a visualisation.
+///
+/// The gain is converted to decibels and the range `floor_db..=0dB` is mapped linearly onto
+/// `0.0..=1.0`. Gains below `floor_db` (including `0.0`) are clamped to `0.0` and gains above unity
+/// are clamped to `1.0`. `floor_db` should be negative, e.g. `-60.0`.
+#[cfg(feature = "full")]
+pub fn gain_to_intensity<S>(gain: S, floor_db: S) -> S
+where
+    S: Scalar + Float,
+{
+    let db = S::from_f32(20.0) * gain.log10();
+    let intensity = S::one() - db / floor_db;
+    intensity.max(S::zero()).min(S::one())
+}
+
+/// Compute the gain of each speaker for each of the given rolloff values.
+///
+/// Returns one row of gains per rolloff, in the same order as `rolloffs`. The distances are only
+/// computed once and are shared across the whole sweep.
+///
+/// Each row is produced by `modelled_gains` with a `RolloffModel::Constant`, as the rolloff cancels
+/// out of the gains produced by `SpeakerGains`.
+///
+/// **Panics** if `speakers` is empty.
+#[cfg(all(feature = "full", feature = "alloc"))]
+pub fn rolloff_sweep_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    source: [S; 2],
+    blur: S,
+    rolloffs: &[S],
+) -> Vec<Vec<S>>
+where
+    S: Scalar + Float,
+{
+    let spkrs = speakers_2(speakers, source, blur);
+    rolloffs
+        .iter()
+        .map(|&r| {
+            let mut row = vec![S::from_f32(0.0); spkrs.len()];
+            modelled_gains(&spkrs, &RolloffModel::Constant(r), &mut row);
+            row
+        })
+        .collect()
+}
+
+/// The rolloff for a source moving at the given speed, interpolated linearly from `slow_rolloff`
+/// for a stationary source to `fast_rolloff` for a source moving at `max_speed` or faster, where
+/// `speed` and `max_speed` are expressed in the same units.
+///
+/// Negative speeds are treated as stationary. If `max_speed` is not positive, `fast_rolloff` is
+/// returned for any speed.
+pub fn speed_adaptive_rolloff<S>(speed: S, slow_rolloff: S, fast_rolloff: S, max_speed: S) -> S
+where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    let one = S::from_f32(1.0);
+    let t = match max_speed > zero {
+        true => speed / max_speed,
+        false => one,
+    };
+    let t = if t < zero {
+        zero
+    } else if t > one {
+        one
+    } else {
+        t
+    };
+    slow_rolloff + (fast_rolloff - slow_rolloff) * t
+}
+
+/// Compute the gains for a moving source, tightening the rolloff as the source moves faster.
+///
+/// The rolloff is determined via `speed_adaptive_rolloff` and the gains are those of
+/// `modelled_gains` with a `RolloffModel::Constant`, such that a faster source concentrates its
+/// gain towards the nearest speakers.
+///
+/// **Panics** if `speakers` is empty or if `out` is not the same length as `speakers`.
+#[cfg(all(feature = "full", feature = "alloc"))]
+#[allow(clippy::too_many_arguments)]
+pub fn gains_speed_adaptive_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    source: [S; 2],
+    speed: S,
+    slow_rolloff: S,
+    fast_rolloff: S,
+    max_speed: S,
+    blur: S,
+    out: &mut [S],
+) where
+    S: Scalar + Float,
+{
+    let rolloff_db = speed_adaptive_rolloff(speed, slow_rolloff, fast_rolloff, max_speed);
+    let spkrs = speakers_2(speakers, source, blur);
+    modelled_gains(&spkrs, &RolloffModel::Constant(rolloff_db), out);
+}
+
+/// Produce a reproducible layout of `n` speakers placed at pseudo-random angles on a ring of the
+/// given radius centred at the origin, each with a weight of `1.0`.
+///
+/// The same `seed` always produces the same layout.
+#[cfg(feature = "random-layouts")]
+pub fn random_ring_2<S>(n: usize, radius: S, seed: u64) -> Vec<PositionedSpeaker<S>>
+where
+    S: Scalar + Float + FloatConst,
+{
+    let mut rng = SplitMix64(seed);
+    (0..n)
+        .map(|_| {
+            let angle = rng.next_scalar::<S>() * S::TAU();
+            let (sin, cos) = angle.sin_cos();
+            PositionedSpeaker {
+                position: [cos * radius, sin * radius],
+                weight: S::one(),
+            }
+        })
+        .collect()
+}
+
+/// Produce a reproducible grid of `cols * rows` speakers, each displaced from its grid point by a
+/// pseudo-random offset of up to `jitter` along each axis and with a weight of `1.0`.
+///
+/// Grid points are separated by `spacing`, beginning at the origin. The same `seed` always
+/// produces the same layout.
+#[cfg(feature = "random-layouts")]
+pub fn random_grid_2<S>(
+    cols: usize,
+    rows: usize,
+    spacing: S,
+    jitter: S,
+    seed: u64,
+) -> Vec<PositionedSpeaker<S>>
+where
+    S: Scalar + Float,
+{
+    let mut rng = SplitMix64(seed);
+    let mut offset = move || (rng.next_scalar::<S>() * S::from_f32(2.0) - S::one()) * jitter;
+    let mut speakers = Vec::with_capacity(cols * rows);
+    for row in 0..rows {
+        for col in 0..cols {
+            let x = S::from_f32(col as f32) * spacing + offset();
+            let y = S::from_f32(row as f32) * spacing + offset();
+            speakers.push(PositionedSpeaker {
+                position: [x, y],
+                weight: S::one(),
+            });
+        }
+    }
+    speakers
+}
+
+// A tiny, deterministic pseudo-random number generator for producing reproducible layouts.
+#[cfg(feature = "random-layouts")]
+struct SplitMix64(u64);
+
+#[cfg(feature = "random-layouts")]
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // A value within the range `0.0..1.0`.
+    fn next_scalar<S: Scalar>(&mut self) -> S {
+        let unit = (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+        S::from_f32(unit)
+    }
+}
+
+/// Compute the gains for each source position along the given path, e.g. for offline rendering
+/// of an automation curve.
+///
+/// A single `DistanceBuffer` is reused for the distances of every position, such that the only
+/// allocation per position is the yielded gain vector.
+///
+/// **Panics** if `speakers` is empty and `path` yields a position.
+#[cfg(feature = "alloc")]
+pub fn gains_over_path_2<S, I>(
+    speakers: &[PositionedSpeaker<S>],
+    path: I,
+    rolloff_db: S,
+    blur: S,
+) -> impl Iterator<Item = Vec<S>>
+where
+    S: Scalar,
+    I: IntoIterator<Item = [S; 2]>,
+{
+    let mut buffer = DistanceBuffer::new(speakers, blur);
+    path.into_iter().map(move |p| {
+        buffer.update(p);
+        buffer.gains(rolloff_db).collect()
+    })
+}
+
+/// Compute the gains for each of the given source positions in parallel via `rayon`, e.g. for
+/// batch rendering many sources against a single layout.
+///
+/// The `a` coefficient is computed once for the whole batch, while the distances and `k` are
+/// computed per source. Each `rayon` job reuses its own buffer of distances. The rows of gains are
+/// produced in the same order as `sources`.
+///
+/// **Panics** if `speakers` is empty and `sources` is not.
+#[cfg(feature = "rayon")]
+pub fn par_gains_batch<S>(
+    speakers: &[PositionedSpeaker<S>],
+    sources: &[[S; 2]],
+    rolloff_db: S,
+    blur: S,
+) -> Vec<Vec<S>>
+where
+    S: Scalar + Send + Sync,
+{
+    use rayon::prelude::*;
+    let a = a_coefficient(rolloff_db);
+    sources
+        .par_iter()
+        .map_init(
+            || Vec::with_capacity(speakers.len()),
+            |spkrs, &source| {
+                spkrs.clear();
+                spkrs.extend(speakers.iter().map(|s| s.speaker(source, blur)));
+                SpeakerGains::with_a_coefficient(spkrs, a).collect()
+            },
+        )
+        .collect()
+}
+
+/// The indices of the speakers whose peak gain across every source position along the given path
+/// stays below `threshold`, indicating that they are effectively unused.
+///
+/// If `path` yields no positions, every speaker is considered underutilised.
+///
+/// **Panics** if `speakers` is empty and `path` yields a position.
+#[cfg(feature = "alloc")]
+pub fn underutilized_speakers_2<S, I>(
+    speakers: &[PositionedSpeaker<S>],
+    path: I,
+    rolloff_db: S,
+    blur: S,
+    threshold: S,
+) -> Vec<usize>
+where
+    S: Scalar,
+    I: IntoIterator<Item = [S; 2]>,
+{
+    let mut peaks = vec![S::from_f32(0.0); speakers.len()];
+    for gains in gains_over_path_2(speakers, path, rolloff_db, blur) {
+        for (peak, g) in peaks.iter_mut().zip(gains) {
+            if g > *peak {
+                *peak = g;
+            }
+        }
+    }
+    peaks
+        .iter()
+        .enumerate()
+        .filter(|&(_, &peak)| peak < threshold)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Compute the gains of the physical speakers for a source panned across a denser layout of
+/// virtual speakers, e.g. when authoring against a virtual grid for a sparse physical array.
+///
+/// The source is first panned across the virtual speakers via DBAP. Each virtual speaker is then
+/// itself treated as a source and panned across the physical speakers, forming a projection
+/// matrix `Pᵥᵢ` from virtual speaker `v` to physical speaker `i`. The resulting gain of each
+/// physical speaker is `Σᵥ(gᵥ * Pᵥᵢ)`. A virtual speaker coincident with a physical speaker is
+/// projected solely onto that speaker, such that a virtual layout identical to the physical layout
+/// reproduces the direct gains.
+///
+/// **Panics** if `physical` or `virtual_speakers` is empty or if `out` is not the same length as
+/// `physical`.
+#[cfg(feature = "alloc")]
+pub fn interpolate_virtual_gains_2<S>(
+    physical: &[PositionedSpeaker<S>],
+    virtual_speakers: &[PositionedSpeaker<S>],
+    source: [S; 2],
+    rolloff_db: S,
+    blur: S,
+    out: &mut [S],
+) where
+    S: Scalar,
+{
+    assert_eq!(physical.len(), out.len());
+    let zero = S::from_f32(0.0);
+    for o in out.iter_mut() {
+        *o = zero;
+    }
+    let virtual_spkrs = speakers_2(virtual_speakers, source, blur);
+    let virtual_gains = SpeakerGains::new(&virtual_spkrs, rolloff_db);
+    for (v, g) in virtual_speakers.iter().zip(virtual_gains) {
+        if g == zero {
+            continue;
+        }
+        let coincident = physical.iter().position(|p| p.position == v.position);
+        match coincident {
+            Some(i) => out[i] = out[i] + g,
+            None => {
+                let spkrs = speakers_2(physical, v.position, blur);
+                for (o, p) in out.iter_mut().zip(SpeakerGains::new(&spkrs, rolloff_db)) {
+                    *o = *o + g * p;
+                }
+            }
+        }
+    }
+}
+
+/// The derivative of the total power `Σ(gᵢ²)` of the gains for a source at the given position with
+/// respect to the rolloff in dB.
+///
+/// The gains are those of `modelled_gains` with a `RolloffModel::Constant`, i.e.
+/// `gᵢ = w̄ wᵢ tᵢ / Σ(wⱼ² tⱼ)` where `tᵢ = dᵢ^log₂(a)`. As `∂ln(tᵢ)/∂r = -ln(dᵢ) log₂(10) / 20`,
+/// each `∂ln(gᵢ)/∂r` is `∂ln(tᵢ)/∂r` less its mean weighted by each speaker's share `wⱼ gⱼ / w̄`,
+/// and the derivative is `Σ(2gᵢ² ∂ln(gᵢ)/∂r)`. A speaker coincident with the source (e.g. an
+/// unblurred speaker) produces a non-finite derivative.
+///
+/// Note that the rolloff cancels out of the gains produced by `SpeakerGains`, the total power of
+/// which has a derivative of `0.0` for all rolloffs.
+///
+/// **Panics** if `speakers` is empty.
+#[cfg(all(feature = "full", feature = "alloc"))]
+pub fn total_power_drolloff_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    source: [S; 2],
+    blur: S,
+    rolloff_db: S,
+) -> S
+where
+    S: Scalar + Float,
+{
+    let zero = S::from_f32(0.0);
+    let spkrs = speakers_2(speakers, source, blur);
+    let mean_weight = mean_weight(spkrs.iter().map(|s| s.weight));
+    if mean_weight == zero {
+        return zero;
+    }
+    let mut gains = vec![zero; spkrs.len()];
+    modelled_gains(&spkrs, &RolloffModel::Constant(rolloff_db), &mut gains);
+    let scale = S::from_f32(10.0).log2() / S::from_f32(20.0);
+    let dln_t = |s: &Speaker<S>| -s.distance.ln() * scale;
+    let mean_dln_t = spkrs
+        .iter()
+        .zip(&gains)
+        .map(|(s, &g)| s.weight * g * dln_t(s))
+        .sum::<S>()
+        / mean_weight;
+    let two = S::from_f32(2.0);
+    spkrs
+        .iter()
+        .zip(&gains)
+        .map(|(s, &g)| two * g * g * (dln_t(s) - mean_dln_t))
+        .sum()
+}
+
+/// The largest rolloff for which the gain of every speaker for a source at the given position is
+/// at least `floor`.
+///
+/// The gains are those of `modelled_gains` with a `RolloffModel::Constant`, within which a greater
+/// rolloff concentrates the gain towards the nearest speakers. The search assumes that the quietest
+/// speaker only grows quieter as the rolloff rises, as is the case for speakers of equal weight. An
+/// upper bound is found by doubling the rolloff until the floor is broken, after which the bounds
+/// are bisected until they can no longer be split, such that the floor is met at the returned
+/// rolloff but not at any representable rolloff above it.
+///
+/// Returns `0.0` if the floor is not met even without any rolloff. If the floor is met for every
+/// rolloff, e.g. where all speakers are equidistant from the source, the greatest finite rolloff
+/// reached by the doubling is returned.
+///
+/// **Panics** if `speakers` is empty.
+#[cfg(all(feature = "full", feature = "alloc"))]
+pub fn max_rolloff_for_floor_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    source: [S; 2],
+    blur: S,
+    floor: S,
+) -> S
+where
+    S: Scalar + Float,
+{
+    let zero = S::from_f32(0.0);
+    let two = S::from_f32(2.0);
+    let mut spkrs = speakers_2(speakers, source, blur);
+    // A constant rolloff is independent of the unit of distance. Measuring from the nearest speaker
+    // keeps every attenuation within `0.0..=1.0`, avoiding overflow for large rolloffs.
+    let nearest = spkrs
+        .iter()
+        .map(|s| s.distance)
+        .filter(|&d| d != zero)
+        .fold(S::infinity(), S::min);
+    if Scalar::is_finite(nearest) {
+        for s in &mut spkrs {
+            s.distance = s.distance / nearest;
+        }
+    }
+    let mut gains = vec![zero; spkrs.len()];
+    let mut meets_floor = |rolloff_db| {
+        modelled_gains(&spkrs, &RolloffModel::Constant(rolloff_db), &mut gains);
+        gains.iter().all(|&g| g >= floor)
+    };
+    let (mut lo, mut hi) = (zero, S::from_f32(1.0));
+    if !meets_floor(lo) {
+        return lo;
+    }
+    while meets_floor(hi) {
+        lo = hi;
+        hi = hi * two;
+        if !Scalar::is_finite(hi) {
+            return lo;
+        }
+    }
+    loop {
+        let mid = lo + (hi - lo) / two;
+        if mid == lo || mid == hi {
+            return lo;
+        }
+        match meets_floor(mid) {
+            true => lo = mid,
+            false => hi = mid,
+        }
+    }
+}
+
+/// The total power `Σ(gᵢ²)` of the given gains.
+pub fn total_power<S>(gains: &[S]) -> S
+where
+    S: Scalar,
+{
+    gains.iter().map(|&g| g * g).sum()
+}
+
+/// The centre of each cell of a `resolution * resolution` grid spanning the bounding box of the
+/// speakers.
+///
+/// Cells are produced in row-major order, such that the cell at column `c` and row `r` lies at
+/// index `r * resolution + c`, with column `0` and row `0` nearest to the minimum corner.
+///
+/// **Panics** if `speakers` is empty.
+#[cfg(feature = "alloc")]
+pub fn grid_points_2<S>(speakers: &[PositionedSpeaker<S>], resolution: usize) -> Vec<[S; 2]>
+where
+    S: Scalar,
+{
+    let (min, max) = bounding_box_2(speakers);
+    let res = S::from_f32(resolution as f32);
+    let size = [(max[0] - min[0]) / res, (max[1] - min[1]) / res];
+    let half = S::from_f32(0.5);
+    let mut points = Vec::with_capacity(resolution * resolution);
+    for row in 0..resolution {
+        for col in 0..resolution {
+            let (c, r) = (S::from_f32(col as f32), S::from_f32(row as f32));
+            points.push([min[0] + (c + half) * size[0], min[1] + (r + half) * size[1]]);
+        }
+    }
+    points
+}
+
+/// Produce a compensation gain for each cell of the grid described by `grid_points_2` that
+/// flattens the total power of the field across the space.
+///
+/// Uneven speaker spacing causes the total power of the gains to vary as a source moves. Each
+/// compensation gain `cᵢ = sqrt(mean(P) / Pᵢ)` scales the gains of a source within that cell such
+/// that its total power is equal to the mean total power across the grid. Cells with a total
+/// power of `0.0` receive a compensation of `1.0`.
+///
+/// **Panics** if `speakers` is empty.
+#[cfg(feature = "alloc")]
+pub fn loudness_compensation_map_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    rolloff_db: S,
+    blur: S,
+    grid_resolution: usize,
+) -> Vec<S>
+where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    let mut buffer = DistanceBuffer::new(speakers, blur);
+    let powers: Vec<S> = grid_points_2(speakers, grid_resolution)
+        .into_iter()
+        .map(|p| {
+            buffer.update(p);
+            let gains: Vec<S> = buffer.gains(rolloff_db).collect();
+            total_power(&gains)
+        })
+        .collect();
+    let n = S::from_f32(powers.len() as f32);
+    let mean = powers.iter().cloned().sum::<S>() / n;
+    powers
+        .into_iter()
+        .map(|p| match p == zero {
+            true => S::from_f32(1.0),
+            false => (mean / p).pow(S::from_f32(0.5)),
+        })
+        .collect()
+}
+
+/// Produce a weight for each speaker that emphasises the speakers covering the audience area,
+/// described by the vertices of `audience_polygon`.
+///
+/// The polygon is sampled at the centre of each cell of a `resolution * resolution` grid spanning
+/// its bounding box, retaining the samples that lie inside it. Each speaker's coverage is its mean
+/// share of the power `gᵢ²` for a source at each sample, computed via `SpeakerGains` with the given
+/// rolloff, such that speakers near the audience receive the greatest weights. The weights are
+/// scaled such that the greatest is `1.0` and can be assigned directly to `Speaker::weight`.
+///
+/// If no samples lie inside the polygon (e.g. it has fewer than three vertices) every speaker
+/// receives a weight of `1.0`.
+///
+/// **Panics** if `speakers` is empty.
+#[cfg(feature = "alloc")]
+pub fn audience_weights_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    audience_polygon: &[[S; 2]],
+    rolloff_db: S,
+    blur: S,
+    resolution: usize,
+) -> Vec<S>
+where
+    S: Scalar,
+{
+    assert!(!speakers.is_empty());
+    if audience_polygon.is_empty() {
+        return vec![S::from_f32(1.0); speakers.len()];
+    }
+    let zero = S::from_f32(0.0);
+    let mut coverage = vec![zero; speakers.len()];
+    let vertices: Vec<_> = audience_polygon
+        .iter()
+        .map(|&position| PositionedSpeaker {
+            position,
+            weight: S::from_f32(1.0),
+        })
+        .collect();
+    let mut buffer = DistanceBuffer::new(speakers, blur);
+    let samples = grid_points_2(&vertices, resolution)
+        .into_iter()
+        .filter(|&p| point_in_polygon_2(p, audience_polygon));
+    for p in samples {
+        buffer.update(p);
+        for (c, g) in coverage.iter_mut().zip(buffer.gains(rolloff_db)) {
+            *c = *c + g * g;
+        }
+    }
+    let max = coverage
+        .iter()
+        .fold(zero, |max, &c| if c > max { c } else { max });
+    if max == zero {
+        return vec![S::from_f32(1.0); speakers.len()];
+    }
+    for c in coverage.iter_mut() {
+        *c = *c / max;
+    }
+    coverage
+}
+
+/// The centre of the cell of the grid described by `grid_points_2` for which the gains of a
+/// source are closest to uniform across all speakers, e.g. for positioning ambience beds.
+///
+/// Uniformity is measured by the variance of the gains, with ties resolved in favour of the first
+/// cell in row-major order.
+///
+/// **Panics** if `speakers` is empty or if `grid_resolution` is `0`.
+#[cfg(feature = "alloc")]
+pub fn most_uniform_source_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    rolloff_db: S,
+    blur: S,
+    grid_resolution: usize,
+) -> [S; 2]
+where
+    S: Scalar,
+{
+    let n = S::from_f32(speakers.len() as f32);
+    let mut buffer = DistanceBuffer::new(speakers, blur);
+    let variance = |gains: &[S]| {
+        let mean = gains.iter().cloned().sum::<S>() / n;
+        gains.iter().map(|&g| (g - mean) * (g - mean)).sum::<S>() / n
+    };
+    let mut points = grid_points_2(speakers, grid_resolution).into_iter();
+    let first = points
+        .next()
+        .expect("grid_resolution must be greater than 0");
+    let first_variance = {
+        buffer.update(first);
+        variance(&buffer.gains(rolloff_db).collect::<Vec<_>>())
+    };
+    points
+        .fold((first, first_variance), |min, p| {
+            buffer.update(p);
+            let v = variance(&buffer.gains(rolloff_db).collect::<Vec<_>>());
+            match v < min.1 {
+                true => (p, v),
+                false => min,
+            }
+        })
+        .0
+}
+
+/// Select a small subset of the candidate speakers such that every point of `grid` lies within
+/// reach of a selected speaker, e.g. for installation planning.
+///
+/// Coverage is measured by the attenuation `d^log₂(a)` that `modelled_gains` applies to a speaker
+/// at the speaker's blurred distance `d` from a point, where `a` is the `a_coefficient` of the
+/// rolloff. The level therefore falls by the rolloff with each doubling of distance and a speaker
+/// at a distance of `1.0` is unattenuated. The attenuation is used prior to the normalisation
+/// across the active speakers, as normalised gains depend upon the whole subset (a lone speaker
+/// always receives a gain of `1.0`) and would not measure how well a speaker reaches a point. A
+/// point is covered where the attenuation of a selected speaker is at least `floor`.
+///
+/// Finding the smallest such subset is a set-cover problem, so a greedy heuristic is used: the
+/// candidate covering the most points not yet covered (preferring the lowest index in the case of
+/// a tie) is selected until every point is covered. The result is not guaranteed to be minimal,
+/// though it is within a logarithmic factor of the minimum. If some points cannot be covered by
+/// any candidate, the returned subset covers every point that can be. Indices are returned in the
+/// order in which they were selected.
+///
+/// **Panics** if `candidates` is empty.
+#[cfg(all(feature = "full", feature = "alloc"))]
+pub fn min_speaker_subset_2<S>(
+    candidates: &[PositionedSpeaker<S>],
+    grid: &[[S; 2]],
+    rolloff_db: S,
+    blur: S,
+    floor: S,
+) -> Vec<usize>
+where
+    S: Scalar + Float,
+{
+    assert!(!candidates.is_empty());
+    let a = a_coefficient(rolloff_db);
+    let covers: Vec<Vec<bool>> = candidates
+        .iter()
+        .map(|c| {
+            grid.iter()
+                .map(|&p| rolloff_attenuation(c.speaker(p, blur).distance, a) >= floor)
+                .collect()
+        })
+        .collect();
+    let mut covered = vec![false; grid.len()];
+    let mut selected = vec![];
+    loop {
+        let uncovered = |covers: &Vec<bool>| {
+            covers
+                .iter()
+                .zip(&covered)
+                .filter(|&(&covers, &covered)| covers && !covered)
+                .count()
+        };
+        let best = covers
+            .iter()
+            .map(uncovered)
+            .enumerate()
+            .fold((0, 0), |best, (i, n)| match n > best.1 {
+                true => (i, n),
+                false => best,
+            });
+        if best.1 == 0 {
+            return selected;
+        }
+        selected.push(best.0);
+        for (c, &covers) in covered.iter_mut().zip(&covers[best.0]) {
+            *c = *c || covers;
+        }
+    }
+}
+
+/// Whether or not the two slices of gains are the same length and each pair of gains differ by no
+/// more than `epsilon`.
+pub fn gains_approx_eq<S>(a: &[S], b: &[S], epsilon: S) -> bool
+where
+    S: Scalar,
+{
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(&a, &b)| {
+            let diff = a - b;
+            diff <= epsilon && -diff <= epsilon
+        })
+}
+
+/// Label each cell of the grid described by `grid_points_2` with the index of the speaker that
+/// receives the greatest gain for a source at the cell's centre.
+///
+/// This is effectively a DBAP-weighted Voronoi diagram, useful for coverage analysis. Ties are
+/// resolved in favour of the speaker with the lowest index.
+///
+/// **Panics** if `speakers` is empty.
+#[cfg(feature = "alloc")]
+pub fn dominance_map_2<S>(
+    speakers: &[PositionedSpeaker<S>],
+    rolloff_db: S,
+    blur: S,
+    grid_resolution: usize,
+) -> Vec<usize>
+where
+    S: Scalar,
+{
+    let mut buffer = DistanceBuffer::new(speakers, blur);
+    grid_points_2(speakers, grid_resolution)
+        .into_iter()
+        .map(|p| {
+            buffer.update(p);
+            let mut gains = buffer.gains(rolloff_db).enumerate();
+            let first = gains.next().expect("no speakers");
+            gains
+                .fold(first, |max, (i, g)| if g > max.1 { (i, g) } else { max })
+                .0
+        })
+        .collect()
+}
+
+/// Merge two layouts of speakers into one, e.g. when combining two installations within a venue.
+///
+/// The speakers of `a` are followed by those of `b`. Any speaker of `b` lying within
+/// `dedup_radius` of a speaker already within the merged layout is considered coincident with it.
+/// Coincident pairs are merged into the single physical speaker that already exists within the
+/// layout, retaining its position and taking the greater of the two weights so that neither
+/// installation's emphasis is lost. A `dedup_radius` of `0.0` only merges exactly coincident
+/// speakers.
+#[cfg(feature = "alloc")]
+pub fn merge_layouts<S>(
+    a: &[PositionedSpeaker<S>],
+    b: &[PositionedSpeaker<S>],
+    dedup_radius: S,
+) -> Vec<PositionedSpeaker<S>>
+where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    let radius2 = dedup_radius * dedup_radius;
+    let mut merged = a.to_vec();
+    for s in b {
+        let duplicate = merged
+            .iter()
+            .position(|m| blurred_distance_2(m.position, s.position, zero) <= radius2);
+        match duplicate {
+            Some(i) if s.weight > merged[i].weight => merged[i].weight = s.weight,
+            Some(_) => (),
+            None => merged.push(*s),
+        }
+    }
+    merged
+}
+
+/// A summary of the differences between two versions of a speaker layout, produced via
+/// `layout_diff_2`.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutDiff<S = DefaultScalar> {
+    /// The displacement `new - old` of each speaker's position.
+    pub position_deltas: Vec<[S; 2]>,
+    /// The distance moved by each speaker.
+    pub displacements: Vec<S>,
+    /// The change `new - old` in each speaker's weight.
+    pub weight_deltas: Vec<S>,
+    /// The change `new - old` in each speaker's gain for the reference source.
+    pub gain_deltas: Vec<S>,
+}
+
+#[cfg(feature = "alloc")]
+impl<S> LayoutDiff<S>
+where
+    S: Scalar,
+{
+    /// The indices of the speakers whose position changed.
+    pub fn moved(&self) -> Vec<usize> {
+        let zero = S::from_f32(0.0);
+        (0..self.displacements.len())
+            .filter(|&i| self.displacements[i] != zero)
+            .collect()
+    }
+}
+
+/// Compare two versions of a speaker layout, summarising how each speaker moved and the resulting
+/// change in gains for a source at `reference_source`.
+///
+/// Speakers are compared by index. Note that moving a single speaker changes the `k` coefficient
+/// and in turn the gain of *every* speaker, so unmoved speakers generally show a small gain delta
+/// too.
+///
+/// **Panics** if `old` is empty or if `new` is not the same length as `old`.
+#[cfg(feature = "alloc")]
+pub fn layout_diff_2<S>(
+    old: &[PositionedSpeaker<S>],
+    new: &[PositionedSpeaker<S>],
+    reference_source: [S; 2],
+    rolloff_db: S,
+    blur: S,
+) -> LayoutDiff<S>
+where
+    S: Scalar,
+{
+    assert_eq!(old.len(), new.len());
+    let zero = S::from_f32(0.0);
+    let origin = [zero, zero];
+    let position_deltas: Vec<[S; 2]> = old
+        .iter()
+        .zip(new)
+        .map(|(o, n)| [n.position[0] - o.position[0], n.position[1] - o.position[1]])
+        .collect();
+    let displacements = position_deltas
+        .iter()
+        .map(|&d| blurred_distance_2(origin, d, zero).pow(S::from_f32(0.5)))
+        .collect();
+    let weight_deltas = old
+        .iter()
+        .zip(new)
+        .map(|(o, n)| n.weight - o.weight)
+        .collect();
+    let gains = |speakers| {
+        let spkrs = speakers_2(speakers, reference_source, blur);
+        SpeakerGains::new(&spkrs, rolloff_db).collect::<Vec<_>>()
+    };
+    let (old_gains, new_gains) = (gains(old), gains(new));
+    let gain_deltas = old_gains
+        .iter()
+        .zip(&new_gains)
+        .map(|(&o, &n)| n - o)
+        .collect();
+    LayoutDiff {
+        position_deltas,
+        displacements,
+        weight_deltas,
+        gain_deltas,
+    }
+}
+
+/// Models for the rolloff applied to each speaker by distance.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RolloffModel<S = DefaultScalar> {
+    /// A single rolloff in decibels per doubling of distance, applied to all speakers.
+    Constant(S),
+    /// Distinct near-field and far-field rolloffs that meet at a knee distance.
+    ///
+    /// The rolloff in dB for a speaker at distance `d` is blended via
+    /// `near_db + (far_db - near_db) * d² / (d² + knee_distance²)`. This is smooth across all
+    /// distances, tends towards `near_db` well within the knee and towards `far_db` well beyond it,
+    /// and lies exactly halfway between the two at `knee_distance`.
+    Knee {
+        /// The rolloff applied within the near-field.
+        near_db: S,
+        /// The rolloff applied within the far-field.
+        far_db: S,
+        /// The distance at which the near and far fields meet.
+        knee_distance: S,
+    },
+}
+
+impl<S> RolloffModel<S>
+where
+    S: Scalar,
+{
+    /// The rolloff in dB applied to a speaker at the given distance.
+    pub fn rolloff_db(&self, distance: S) -> S {
+        match *self {
+            RolloffModel::Constant(db) => db,
+            RolloffModel::Knee {
+                near_db,
+                far_db,
+                knee_distance,
+            } => {
+                let d2 = distance * distance;
+                let t = d2 / (d2 + knee_distance * knee_distance);
+                near_db + (far_db - near_db) * t
+            }
+        }
+    }
+}
+
+/// Compute the gains for the given speakers where the rolloff of each speaker is determined by its
+/// distance via the given rolloff model.
+///
+/// Unlike `SpeakerGains`, within which the `a` coefficient cancels out of the gains, the amplitude
+/// of each speaker here falls by its rolloff for every doubling of its distance. A speaker at
+/// distance `dᵢ` with coefficient `aᵢ` is attenuated by `tᵢ = dᵢ^log₂(aᵢ)` and its gain is
+/// `w̄ wᵢ tᵢ / Σ(wⱼ² tⱼ)`, such that the invariant described by `dbap_energy` continues to hold. A
+/// greater rolloff therefore always lowers the share of a speaker relative to nearer speakers.
+///
+/// Speakers at a distance of `0.0` are excluded from the sum, as within `k_coefficient`.
+///
+/// **Panics** if `out` is not the same length as `speakers`.
+#[cfg(feature = "full")]
+pub fn modelled_gains<S>(speakers: &[Speaker<S>], model: &RolloffModel<S>, out: &mut [S])
+where
+    S: Scalar + Float,
+{
+    let a = |s: &Speaker<S>| a_coefficient(model.rolloff_db(s.distance));
+    attenuated_gains(speakers, |s| rolloff_attenuation(s.distance, a(s)), out);
+}
+
+// The gains `w̄ wᵢ tᵢ / Σ(wⱼ² tⱼ)` for the given per-speaker attenuation `tᵢ`, excluding speakers
+// at a distance of `0.0` from the sum.
+#[cfg(feature = "full")]
+fn attenuated_gains<S, F>(speakers: &[Speaker<S>], attenuation: F, out: &mut [S])
+where
+    S: Scalar + Float,
+    F: Fn(&Speaker<S>) -> S,
+{
+    assert_eq!(speakers.len(), out.len());
+    let zero = S::from_f32(0.0);
+    let sum: S = speakers
+        .iter()
+        .filter(|s| s.distance != zero)
+        .map(|s| s.weight * s.weight * attenuation(s))
+        .sum();
+    let mean_weight = mean_weight(speakers.iter().map(|s| s.weight));
+    for (o, s) in out.iter_mut().zip(speakers) {
+        *o = if sum == zero {
+            zero
+        } else {
+            mean_weight * s.weight * attenuation(s) / sum
+        };
+    }
+}
+
+// The attenuation `d^log₂(a)` of a speaker at the given distance, such that its amplitude falls by
+// the rolloff described by `a` for every doubling of the distance.
+#[cfg(feature = "full")]
+fn rolloff_attenuation<S>(distance: S, a_coefficient: S) -> S
+where
+    S: Scalar + Float,
+{
+    distance.powf(a_coefficient.log2())
+}
+
+/// Limit each gain to `threshold`, redistributing the excess energy among the remaining gains.
+///
+/// The algorithm iteratively:
+///
+/// 1. clamps every gain exceeding `threshold` to `threshold`, then
+/// 2. scales all unclamped gains by a common factor such that the total power `Σ(gᵢ²)` is equal to
+///    its original value.
+///
+/// This repeats until no further gains exceed the threshold, requiring at most one iteration per
+/// gain. Scaling the unclamped gains by a common factor preserves their relative balance, keeping
+/// the perceived position roughly intact.
+///
+/// Total power is preserved whenever it is feasible to do so, i.e. when
+/// `gains.len() * threshold² >= Σ(gᵢ²)` and enough gains are non-zero to carry the excess.
+/// Otherwise, as much power as possible is preserved with all non-zero gains ending at the
+/// threshold.
+///
+/// See `limit_and_redistribute_soft_knee` for a limiter whose gain reduction begins gradually below
+/// the threshold.
+#[cfg(feature = "alloc")]
+pub fn limit_and_redistribute<S>(gains: &mut [S], threshold: S)
+where
+    S: Scalar,
+{
+    limit_and_redistribute_soft_knee(gains, threshold, S::from_f32(0.0))
+}
+
+/// The same as `limit_and_redistribute`, but limits each gain via a soft knee of the given width.
+///
+/// Gains below `threshold - knee / 2` pass unchanged, while gains above `threshold + knee / 2` are
+/// limited to `threshold`. Within the knee, each gain `g` becomes
+/// `g - (g - threshold + knee / 2)² / (2 * knee)`, meeting both neighbouring segments with a
+/// matching slope. The gain reduction therefore grows continuously with the input, rather than
+/// switching on abruptly at the threshold. A `knee` of `0.0` (or less) is equivalent to
+/// `limit_and_redistribute`.
+///
+/// Each gain exceeding the start of the knee is limited via the curve above, after which the
+/// remaining gains are scaled to preserve the total power as described by
+/// `limit_and_redistribute`. Where preserving the total power is infeasible, all non-zero gains end
+/// at or below the threshold.
+#[cfg(feature = "alloc")]
+pub fn limit_and_redistribute_soft_knee<S>(gains: &mut [S], threshold: S, knee: S)
+where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    let two = S::from_f32(2.0);
+    let knee = if knee > zero { knee } else { zero };
+    let knee_start = threshold - knee / two;
+    let limit = |g: S| {
+        if g >= threshold + knee / two {
+            threshold
+        } else {
+            let over = g - knee_start;
+            g - over * over / (two * knee)
+        }
+    };
+    let energy = total_power(gains);
+    let mut clamped = vec![false; gains.len()];
+    loop {
+        let mut changed = false;
+        for (g, c) in gains.iter_mut().zip(clamped.iter_mut()) {
+            if !*c && *g > knee_start {
+                *g = limit(*g);
+                *c = true;
+                changed = true;
+            }
+        }
+        if !changed {
+            return;
+        }
+        let (clamped_energy, free_energy) =
+            gains
+                .iter()
+                .zip(&clamped)
+                .fold((zero, zero), |(c, f), (&g, &is_clamped)| match is_clamped {
+                    true => (c + g * g, f),
+                    false => (c, f + g * g),
+                });
+        let remaining = energy - clamped_energy;
+        if free_energy == zero || remaining <= zero {
+            return;
+        }
+        let scale = (remaining / free_energy).pow(S::from_f32(0.5));
+        for (g, &c) in gains.iter_mut().zip(&clamped) {
+            if !c {
+                *g = *g * scale;
+            }
+        }
+    }
+}
+
+/// Zero every gain below the threshold of audibility `floor` and redistribute its energy amongst
+/// the remaining gains, e.g. to avoid wasting amplifier headroom and adding noise on speakers that
+/// would be inaudible anyway.
+///
+/// The remaining gains are scaled by a common factor such that the total power `Σ(gᵢ²)` is
+/// preserved, retaining their relative balance. As the remaining gains only ever increase, none
+/// are pushed below the floor by the redistribution.
+///
+/// If every gain lies below `floor`, the gains are left unchanged rather than silencing the source.
+pub fn prune_inaudible<S>(gains: &mut [S], floor: S)
+where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    let total = total_power(gains);
+    let audible: S = gains.iter().filter(|&&g| g >= floor).map(|&g| g * g).sum();
+    if audible == zero {
+        return;
+    }
+    let scale = (total / audible).pow(S::from_f32(0.5));
+    for g in gains.iter_mut() {
+        *g = match *g >= floor {
+            true => *g * scale,
+            false => zero,
+        };
+    }
+}
+
+/// Compute the gains for speakers with limited output capability, where `max_gains[i]` is the
+/// greatest gain speaker `i` can reproduce.
+///
+/// Unlike `limit_and_redistribute`, which scales the remaining gains by a common factor, the gain
+/// demanded of capped speakers is shifted towards the speakers with headroom according to DBAP
+/// itself. The algorithm iteratively:
+///
+/// 1. caps every gain exceeding its speaker's `max_gain`, then
+/// 2. recomputes DBAP over the uncapped speakers alone, scaled such that the total power `Σ(gᵢ²)`
+///    is equal to that of the original, uncapped gains.
+///
+/// As the excess is redistributed by distance, the uncapped speakers nearest to the source take on
+/// the most of it. This repeats until no further gains exceed their cap. Each iteration caps at
+/// least one more speaker, so the algorithm converges within one iteration per speaker. If the
+/// caps leave too little headroom to preserve the total power, every speaker ends at its cap.
+///
+/// **Panics** if `speakers` is empty or if `max_gains` and `out` are not the same length as
+/// `speakers`.
+#[cfg(feature = "alloc")]
+pub fn capped_gains<S>(speakers: &[Speaker<S>], max_gains: &[S], rolloff_db: S, out: &mut [S])
+where
+    S: Scalar,
+{
+    assert_eq!(speakers.len(), max_gains.len());
+    assert_eq!(speakers.len(), out.len());
+    let zero = S::from_f32(0.0);
+    for (o, g) in out.iter_mut().zip(SpeakerGains::new(speakers, rolloff_db)) {
+        *o = g;
+    }
+    let energy = total_power(out);
+    let mut spkrs = speakers.to_vec();
+    let mut capped = vec![false; speakers.len()];
+    loop {
+        let mut changed = false;
+        for i in 0..out.len() {
+            if !capped[i] && out[i] > max_gains[i] {
+                out[i] = max_gains[i];
+                spkrs[i].weight = zero;
+                capped[i] = true;
+                changed = true;
+            }
+        }
+        if !changed {
+            return;
+        }
+        fill_remaining_power(&spkrs, &capped, energy, rolloff_db, out);
+    }
+}
+
+/// Compute the gains for the given speakers where each `(index, gain)` pair of `locks` freezes
+/// the speaker at `index` at the given gain.
+///
+/// The locked speakers are excluded from the `k` coefficient, and DBAP is recomputed over the
+/// remaining speakers alone. The remaining gains are then scaled such that the total power
+/// `Σ(gᵢ²)` of all speakers, locked or not, is equal to that of the unlocked gains. If the locks
+/// consume the entire power budget, or none of the remaining speakers has a non-zero weight, the
+/// remaining speakers receive `0.0`.
+///
+/// **Panics** if `speakers` is empty, if a lock refers to an index that is out of range or if `out`
+/// is not the same length as `speakers`.
+#[cfg(feature = "alloc")]
+pub fn locked_gains<S>(speakers: &[Speaker<S>], locks: &[(usize, S)], rolloff_db: S, out: &mut [S])
+where
+    S: Scalar,
+{
+    assert_eq!(speakers.len(), out.len());
+    let energy: S = SpeakerGains::new(speakers, rolloff_db).map(|g| g * g).sum();
+    let mut spkrs = speakers.to_vec();
+    let mut locked = vec![false; speakers.len()];
+    for &(i, gain) in locks {
+        out[i] = gain;
+        spkrs[i].weight = S::from_f32(0.0);
+        locked[i] = true;
+    }
+    fill_remaining_power(&spkrs, &locked, energy, rolloff_db, out);
+}
+
+// Fill the gains of the speakers that are not `fixed` with DBAP over those speakers alone, scaled
+// such that the total power of `out` is equal to `energy`. The weights of `fixed` speakers must
+// already be `0.0`, and their gains already within `out`.
+#[cfg(feature = "alloc")]
+fn fill_remaining_power<S>(
+    speakers: &[Speaker<S>],
+    fixed: &[bool],
+    energy: S,
+    rolloff_db: S,
+    out: &mut [S],
+) where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    let fixed_energy: S = (0..out.len())
+        .filter(|&i| fixed[i])
+        .map(|i| out[i] * out[i])
+        .sum();
+    let remaining = energy - fixed_energy;
+    if fixed.iter().all(|&f| f) || remaining <= zero {
+        for (o, &f) in out.iter_mut().zip(fixed) {
+            if !f {
+                *o = zero;
+            }
+        }
+        return;
+    }
+    let free: Vec<S> = SpeakerGains::new(speakers, rolloff_db).collect();
+    let free_energy: S = (0..out.len())
+        .filter(|&i| !fixed[i])
+        .map(|i| free[i] * free[i])
+        .sum();
+    if free_energy == zero {
+        for i in (0..out.len()).filter(|&i| !fixed[i]) {
+            out[i] = zero;
+        }
+        return;
+    }
+    let scale = (remaining / free_energy).pow(S::from_f32(0.5));
+    for i in (0..out.len()).filter(|&i| !fixed[i]) {
+        out[i] = free[i] * scale;
+    }
+}
+
+// Whether or not the point lies inside the polygon described by the given vertices, via the
+// even-odd rule.
+#[cfg(feature = "alloc")]
+fn point_in_polygon_2<S>(point: [S; 2], polygon: &[[S; 2]]) -> bool
+where
+    S: Scalar,
+{
+    let [x, y] = point;
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for (i, &[xi, yi]) in polygon.iter().enumerate() {
+        let [xj, yj] = polygon[j];
+        // Whether the edge straddles the horizontal ray cast from the point towards positive `x`.
+        if (yi > y) != (yj > y) && x < xi + (y - yi) * (xj - xi) / (yj - yi) {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+// The index of the speaker nearest to the source, preferring the first in the case of a tie.
+//
+// Panics if `speakers` is empty.
+fn nearest_index_2<S>(speakers: &[PositionedSpeaker<S>], source: [S; 2]) -> usize
+where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    let distance = |s: &PositionedSpeaker<S>| blurred_distance_2(source, s.position, zero);
+    let mut nearest = 0;
+    let mut nearest_distance = distance(&speakers[0]);
+    for (i, s) in speakers.iter().enumerate().skip(1) {
+        let d = distance(s);
+        if d < nearest_distance {
+            nearest = i;
+            nearest_distance = d;
+        }
+    }
+    nearest
+}
+
+// Describe each of the positioned speakers by their distance from the given source.
+#[cfg(feature = "alloc")]
+fn speakers_2<S>(speakers: &[PositionedSpeaker<S>], source: [S; 2], blur: S) -> Vec<Speaker<S>>
+where
+    S: Scalar,
+{
+    speakers.iter().map(|s| s.speaker(source, blur)).collect()
+}
+
+/// The gain-weighted average of the speaker positions.
+///
+/// Returns `None` if the gains sum to `0.0`.
+///
+/// **Panics** if `gains` is not the same length as `speakers`.
+pub fn gain_centroid_2<S>(speakers: &[PositionedSpeaker<S>], gains: &[S]) -> Option<[S; 2]>
+where
+    S: Scalar,
+{
+    assert_eq!(speakers.len(), gains.len());
+    let zero = S::from_f32(0.0);
+    let total: S = gains.iter().cloned().sum();
+    if total == zero {
+        return None;
+    }
+    let (x, y) = speakers
+        .iter()
+        .zip(gains)
+        .fold((zero, zero), |(x, y), (s, &g)| {
+            (x + s.position[0] * g, y + s.position[1] * g)
+        });
+    Some([x / total, y / total])
+}
+
+/// The azimuth of the perceived source direction implied by the gains, as seen from `center`.
+///
+/// The angle is in radians within the range `-π..=π`, measured anti-clockwise from the positive `x`
+/// axis. That is, a source hard to the right of `center` yields `0.0` and a source directly in
+/// front (positive `y`) yields `π/2`.
+///
+/// Returns `0.0` if the gains sum to `0.0`.
+///
+/// **Panics** if `gains` is not the same length as `speakers`.
+#[cfg(feature = "full")]
+pub fn perceived_azimuth_2<S>(speakers: &[PositionedSpeaker<S>], gains: &[S], center: [S; 2]) -> S
+where
+    S: Scalar + Float,
+{
+    match gain_centroid_2(speakers, gains) {
+        None => S::zero(),
+        Some([x, y]) => (y - center[1]).atan2(x - center[0]),
+    }
+}
+
+/// An estimate of the apparent width of the phantom source implied by the gains, in the range
+/// `0.0..=1.0`.
+///
+/// The gains are treated as a distribution of power `pᵢ = gᵢ² / Σ(g²)` across the speakers, whose
+/// entropy `H = -Σ(pᵢ·ln pᵢ)` yields the effective number of active speakers `N = exp(H)`. The
+/// width `(N - 1) / (n - 1)` is `0.0` when all power is concentrated in a single speaker and `1.0`
+/// when the power is spread evenly across all `n` speakers.
+///
+/// Returns `0.0` if there are fewer than two gains or if the gains are all `0.0`.
+#[cfg(feature = "full")]
+pub fn apparent_width<S>(gains: &[S]) -> S
+where
+    S: Scalar + Float,
+{
+    let total = total_power(gains);
+    if gains.len() < 2 || total == S::zero() {
+        return S::zero();
+    }
+    let entropy = gains
+        .iter()
+        .map(|&g| g * g / total)
+        .filter(|&p| p > S::zero())
+        .fold(S::zero(), |h, p| h - p * p.ln());
+    let n = S::from_f32(gains.len() as f32);
+    let width = (entropy.exp() - S::one()) / (n - S::one());
+    width.max(S::zero()).min(S::one())
+}
+
+/// Strategies for normalising a set of gains.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Normalization<S = DefaultScalar> {
+    /// Scale the gains such that they sum to `1.0`.
+    ConstantAmplitude,
+    /// Scale the gains such that the sum of their squares is `1.0`.
+    ConstantPower,
+    /// Scale the gains such that the gain of the speaker nearest to the source is equal to the
+    /// given target (typically `1.0`), providing a consistent close-field level reference.
+    ///
+    /// When normalising via `SpeakerGains::normalized` the nearest speaker is determined by
+    /// effective distance. As a result, where min-distance clamping causes multiple speakers to
+    /// share the nearest distance, the first of these is pinned. When normalising a plain slice of
+    /// gains via `normalize`, no distance information is available and the speaker with the
+    /// greatest gain is pinned instead. These are equivalent where all speakers share the same
+    /// weight.
+    PinNearest(S),
+}
+
+/// Normalise the given gains in place using the given strategy.
+///
+/// Gains that are all `0.0` are left unchanged.
+pub fn normalize<S>(gains: &mut [S], mode: Normalization<S>)
+where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    let total = match mode {
+        Normalization::ConstantAmplitude => gains.iter().cloned().sum(),
+        Normalization::ConstantPower => {
+            let sum: S = gains.iter().map(|&g| g * g).sum();
+            sum.pow(S::from_f32(0.5))
+        }
+        Normalization::PinNearest(target) => {
+            let loudest = match gains
+                .iter()
+                .enumerate()
+                .fold(None, |max, (i, &g)| match max {
+                    Some((_, m)) if m >= g => max,
+                    _ => Some((i, g)),
+                }) {
+                None => return,
+                Some((i, _)) => i,
+            };
+            return pin_gain(gains, loudest, target);
+        }
+    };
+    if total == zero {
+        return;
+    }
+    for g in gains {
+        *g = *g / total;
+    }
+}
+
+/// Normalise the gains of each frequency band independently using the given strategy.
+///
+/// Each element of `bands` holds the gain of every speaker for a single band, e.g. as produced by
+/// a separate `SpeakerGains` per band. Normalising each band separately (rather than the summed
+/// broadband gains) keeps the spectral balance intact regardless of the source position. Bands
+/// whose gains are all `0.0` are left unchanged.
+pub fn normalize_per_band<S, B>(bands: &mut [B], mode: Normalization<S>)
+where
+    S: Scalar,
+    B: AsMut<[S]>,
+{
+    for band in bands {
+        normalize(band.as_mut(), mode);
+    }
+}
+
+// Scale the gains such that the gain at index `i` is equal to `target`.
+//
+// Gains are left unchanged if the gain at `i` is `0.0`.
+fn pin_gain<S>(gains: &mut [S], i: usize, target: S)
+where
+    S: Scalar,
+{
+    let pinned = gains[i];
+    if pinned == S::from_f32(0.0) {
+        return;
+    }
+    let scale = target / pinned;
+    for g in gains {
+        *g = *g * scale;
+    }
+}
+
+/// Linearly interpolate between two normalised gain vectors and re-apply the normalisation.
+///
+/// Naively interpolating between two normalised gain vectors does not produce normalised
+/// intermediate vectors, causing a momentary dip in level mid-fade. Re-applying `mode` ensures the
+/// level remains constant throughout the fade.
+///
+/// **Panics** if `from`, `to` and `out` are not all the same length.
+pub fn interpolate_normalized<S>(from: &[S], to: &[S], t: S, mode: Normalization<S>, out: &mut [S])
+where
+    S: Scalar,
+{
+    assert_eq!(from.len(), to.len());
+    assert_eq!(from.len(), out.len());
+    for ((o, &a), &b) in out.iter_mut().zip(from).zip(to) {
+        *o = a + (b - a) * t;
+    }
+    normalize(out, mode);
+}
+
+/// The same as a regular *distance* function but applies a subtle `blur` amount.
+///
+/// From the paper: "In 2D space, blur can be understood as a vertical displacement between source
+/// and speakers. The larger ` gets, the less the source will be able to gravitate towards one
+/// speaker only."
+///
+/// A non-zero blur will ensure that the distance is greater than `0.0` and that we never divide by 0.0.
+pub fn blurred_distance_2<S>(source: [S; 2], speaker: [S; 2], blur: S) -> S
+where
+    S: Scalar,
+{
+    let x = speaker[0] - source[0];
+    let y = speaker[1] - source[1];
+    x * x + y * y + blur * blur
+}
+
+/// The same as `blurred_distance_2` but measures the displacement `d` between the source and
+/// speaker with the given weighting `metric` `M`, producing `dᵀMd + blur²`.
+///
+/// This allows for anisotropic rooms where distance along one axis should count for more than
+/// another. The identity matrix reproduces `blurred_distance_2`.
+///
+/// `metric` must be positive semi-definite, otherwise `dᵀMd` may be negative for some
+/// displacements and the result is not a valid squared distance.
+pub fn mahalanobis_distance_2<S>(source: [S; 2], speaker: [S; 2], metric: [[S; 2]; 2], blur: S) -> S
+where
+    S: Scalar,
+{
+    let x = speaker[0] - source[0];
+    let y = speaker[1] - source[1];
+    let [[m00, m01], [m10, m11]] = metric;
+    x * (m00 * x + m01 * y) + y * (m10 * x + m11 * y) + blur * blur
+}
+
+/// The squared blurred distance between a source and speaker positioned along a ring, taking the
+/// shorter of the two paths around the ring.
+///
+/// Positions are arc-lengths along the ring in the range `0.0..circumference`, allowing a source at
+/// one end of the range to pan seamlessly to speakers at the other. Like `blurred_distance_2`, the
+/// result is squared and includes `blur * blur`.
+pub fn wrapped_distance_1<S>(source: S, speaker: S, circumference: S, blur: S) -> S
+where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    let mut diff = speaker - source;
+    if diff < zero {
+        diff = diff + circumference;
+    }
+    let other = circumference - diff;
+    let d = if other < diff { other } else { diff };
+    d * d + blur * blur
+}
+
+/// The relative amplitude for a speaker where:
+///
+/// - `k` is a coefficient depending on the position of the source and all speakers
+/// - `a` is a coefficient calculated from the rolloff in decibels per doubling distance.
+///
+/// The speaker's `distance` field must be greater than zero or the result will be NaN.
+pub fn v_speaker_relative_amplitude<S>(speaker: &Speaker<S>, k: S, a: S) -> S
+where
+    S: Scalar,
+{
+    k * speaker.weight / ((speaker.distance + speaker.distance) * a)
+}
+
+/// A coefficient calculated from the rolloff `r` in decibels per doubling of distance.
+///
+/// A rolloff of 6dB equals the inverse distance law for sound propagataing in a free field.
+///
+/// For closed or semi-closed environments `r` will generally be lower, in the range 3-5dB, and
+/// depend on reflections and reverberation.
+pub fn a_coefficient<S>(rolloff_db: S) -> S
+where
+    S: Scalar,
+{
+    S::from_f32(10.0).pow(-rolloff_db / S::from_f32(20.0))
+}
+
+/// `k` is a coefficient depending on the position of the source and all speakers.
+///
+/// `k = 2a * w̄ / Σ(wᵢ² / dᵢ²)` where `w̄` is the mean weight of all speakers with a non-zero
+/// weight. Normalising by the mean weight ensures that weights are relative to one another, such
+/// that scaling all weights by the same positive constant leaves the resulting gains unchanged.
+/// Excluding speakers with a weight of `0.0` from the mean ensures that muting a speaker is
+/// equivalent to removing it. Where `w̄` is `1.0` this is the original 2009 formulation, available
+/// for any weights via `KVariant::Standard`.
+///
+/// Returns `0.0` if there were no speakers or if all speakers had a weight or distance of `0.0`.
+pub fn k_coefficient<S>(a: S, speakers: &[Speaker<S>]) -> S
+where
+    S: Scalar,
+{
+    let (mean_weight, sum) = k_sums(speakers.iter().cloned());
+    k_from_sums(a, mean_weight, sum)
+}
+
+// The mean non-zero weight and the sum `Σ(wᵢ² / dᵢ²)` of the given speakers.
+fn k_sums<S, I>(speakers: I) -> (S, S)
+where
+    S: Scalar,
+    I: IntoIterator<Item = Speaker<S>>,
+{
+    let zero = S::from_f32(0.0);
+    let (count, weight_sum, sum) = speakers
+        .into_iter()
+        .fold((0, zero, zero), |(n, w, sum), s| {
+            let n = if s.weight == zero { n } else { n + 1 };
+            (n, w + s.weight, sum + k_term(&s))
+        });
+    (mean(weight_sum, count), sum)
+}
+
+// The mean of all non-zero weights, or `0.0` if there are none.
+fn mean_weight<S, I>(weights: I) -> S
+where
+    S: Scalar,
+    I: IntoIterator<Item = S>,
+{
+    let zero = S::from_f32(0.0);
+    let (count, sum) = weights
+        .into_iter()
+        .filter(|&w| w != zero)
+        .fold((0, zero), |(n, sum), w| (n + 1, sum + w));
+    mean(sum, count)
+}
+
+// The mean of a sum of `count` values, or `0.0` if `count` is `0`.
+fn mean<S>(sum: S, count: usize) -> S
+where
+    S: Scalar,
+{
+    match count {
+        0 => S::from_f32(0.0),
+        n => sum / S::from_f32(n as f32),
+    }
+}
+
+// The `k` coefficient for the given mean weight and sum `Σ(wᵢ² / dᵢ²)`.
+fn k_from_sums<S>(a: S, mean_weight: S, sum: S) -> S
+where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    if sum == zero {
+        zero
+    } else {
+        S::from_f32(2.0) * a * mean_weight / sum
+    }
+}
+
+// The contribution `w² / d²` of a single speaker to the sum within the `k` coefficient, or `0.0`
+// if the speaker's distance is `0.0`.
+fn k_term<S>(s: &Speaker<S>) -> S
+where
+    S: Scalar,
+{
+    let zero = S::from_f32(0.0);
+    if s.distance == zero {
+        return zero;
+    }
+    let w2 = s.weight * s.weight;
+    let d2 = s.distance * s.distance;
+    w2 / d2
+}
+
+/// Alternative formulations of the `k` coefficient, for comparison and research.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum KVariant {
+    /// `2a / Σ(wᵢ² / dᵢ²)`, as per the original 2009 formulation.
+    ///
+    /// This is equivalent to `MeanWeight` when the mean non-zero weight is `1.0`, but otherwise
+    /// depends on the absolute scale of the weights.
+    #[default]
+    Standard,
+    /// `2a * w̄ / Σ(wᵢ² / dᵢ²)` where `w̄` is the mean non-zero weight, as produced by
+    /// `k_coefficient`. This is the formulation used by `SpeakerGains`.
+    MeanWeight,
+    /// `2a / sqrt(Σ(wᵢ² / dᵢ²))`, chosen such that the relative amplitudes satisfy `Σ(vᵢ²) = 1`
+    /// as per the constraint stated in the paper.
+    ConstantPower,
+}
+
+/// The `k` coefficient as computed by the given formulation.
+///
+/// Returns `0.0` if all speakers had a weight or distance of `0.0`.
+pub fn k_coefficient_variant<S>(a: S, speakers: &[Speaker<S>], variant: KVariant) -> S
+where
+    S: Scalar,
+{
+    match variant {
+        KVariant::Standard => {
+            let sum = speakers.iter().map(k_term).sum();
+            k_from_sums(a, S::from_f32(1.0), sum)
+        }
+        KVariant::MeanWeight => k_coefficient(a, speakers),
+        KVariant::ConstantPower => {
+            let zero = S::from_f32(0.0);
+            let sum: S = speakers
+                .iter()
+                .filter(|s| s.distance != zero)
+                .map(|s| (s.weight * s.weight) / (s.distance * s.distance))
+                .sum();
+            if sum == zero {
+                zero
+            } else {
+                S::from_f32(2.0) * a / sum.pow(S::from_f32(0.5))
+            }
+        }
+    }
+}
+
+/// The weighted gain sum `Σ(gainᵢ * weightᵢ) / w̄` that `k_coefficient` is designed to normalise,
+/// where `w̄` is the mean non-zero weight of the speakers.
+///
+/// For gains produced by `SpeakerGains`, `k` is chosen such that this sum is exactly `1.0`
+/// (within floating point precision) for any rolloff, unless `k` was `0.0` in which case it is
+/// `0.0`. This is useful as a correctness oracle when experimenting with variations of the
+/// algorithm.
+///
+/// Note that the paper states its constraint as `Σ(vᵢ²) = 1` over its own relative amplitudes. The
+/// formulation of `k` and `v` used by this crate instead normalises the weighted gain sum.
+///
+/// **Panics** if `gains` is not the same length as `speakers`.
+pub fn dbap_energy<S>(gains: &[S], speakers: &[Speaker<S>]) -> S
+where
+    S: Scalar,
+{
+    assert_eq!(gains.len(), speakers.len());
+    let sum: S = gains.iter().zip(speakers).map(|(&g, s)| g * s.weight).sum();
+    sum / mean_weight(speakers.iter().map(|s| s.weight))
+}
+
+// A square layout of four unit-weight speakers with corners at the origin and `[size, size]`.
+#[cfg(test)]
+fn square_2<S: Scalar>(size: f32) -> [PositionedSpeaker<S>; 4] {
+    let (zero, size) = (S::from_f32(0.0), S::from_f32(size));
+    let corners = [[zero, zero], [size, zero], [size, size], [zero, size]];
+    corners.map(|position| PositionedSpeaker {
+        position,
+        weight: S::from_f32(1.0),
+    })
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_gains() {
+    fn magnitude2<S>([x, y]: [S; 2]) -> S
+    where
+        S: Copy + Add<S, Output = S> + Mul<S, Output = S>,
+    {
+        x * x + y * y
+    }
+
+    fn distance2<S>([ax, ay]: [S; 2], [bx, by]: [S; 2]) -> S
+    where
+        S: Copy + Add<S, Output = S> + Mul<S, Output = S> + Sub<S, Output = S>,
+    {
+        magnitude2([bx - ax, by - ay])
+    }
+
+    let src = [5f64, 5.0];
+    let speaker = |v: [f64; 2], w| Speaker::new(distance2(v, src).sqrt(), w);
+    let a = speaker([0.0, 0.0], 1.0);
+    let b = speaker([10.0, 0.0], 1.0);
+    let c = speaker([10.0, 10.0], 1.0);
+    let d = speaker([0.0, 10.0], 1.0);
+    let spkrs = vec![a, b, c, d];
+    let r = 6.0; // free-field rolloff db.
+    let gains = SpeakerGains::new(&spkrs, r).collect::<Vec<_>>();
+    let g = gains[0];
     for gain in gains {
         assert_eq!(g, gain);
     }
 }
+
+#[cfg(all(feature = "full", feature = "alloc"))]
+#[test]
+fn blend_positions() {
+    let spkrs = square_2(10.0);
+    let (pos_a, pos_b) = ([2f64, 3.0], [8.0, 6.0]);
+    let (r, blur) = (6.0, 0.1);
+    let field = |pos| {
+        let s: Vec<_> = spkrs.iter().map(|s| s.speaker(pos, blur)).collect();
+        SpeakerGains::new(&s, r).collect::<Vec<_>>()
+    };
+    let mut out = [0.0; 4];
+    blend_positions_2(&spkrs, pos_a, pos_b, 0.0, r, blur, &mut out);
+    for (o, g) in out.iter().zip(field(pos_a)) {
+        assert!((o - g).abs() < 1e-12);
+    }
+    blend_positions_2(&spkrs, pos_a, pos_b, 1.0, r, blur, &mut out);
+    for (o, g) in out.iter().zip(field(pos_b)) {
+        assert!((o - g).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn scalar_finiteness() {
+    assert!(Scalar::is_finite(1f32));
+    assert!(Scalar::is_finite(-1e300f64));
+    assert!(!Scalar::is_finite(f32::INFINITY));
+    assert!(!Scalar::is_finite(f64::NEG_INFINITY));
+    assert!(!Scalar::is_finite(f64::NAN));
+    assert!(Scalar::is_nan(f32::NAN));
+    assert!(!Scalar::is_nan(f64::INFINITY));
+}
+
+#[test]
+fn custom_scalar() {
+    #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+    struct Wrapper(f64);
+
+    impl Scalar for Wrapper {
+        fn from_f32(f: f32) -> Self {
+            Wrapper(f.into())
+        }
+    }
+    impl Add for Wrapper {
+        type Output = Self;
+        fn add(self, rhs: Self) -> Self {
+            Wrapper(self.0 + rhs.0)
+        }
+    }
+    impl Div for Wrapper {
+        type Output = Self;
+        fn div(self, rhs: Self) -> Self {
+            Wrapper(self.0 / rhs.0)
+        }
+    }
+    impl Mul for Wrapper {
+        type Output = Self;
+        fn mul(self, rhs: Self) -> Self {
+            Wrapper(self.0 * rhs.0)
+        }
+    }
+    impl Neg for Wrapper {
+        type Output = Self;
+        fn neg(self) -> Self {
+            Wrapper(-self.0)
+        }
+    }
+    impl Pow<Wrapper> for Wrapper {
+        type Output = Self;
+        fn pow(self, rhs: Self) -> Self {
+            Wrapper(self.0.powf(rhs.0))
+        }
+    }
+    impl Sub for Wrapper {
+        type Output = Self;
+        fn sub(self, rhs: Self) -> Self {
+            Wrapper(self.0 - rhs.0)
+        }
+    }
+    impl Sum for Wrapper {
+        fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+            Wrapper(iter.map(|w| w.0).sum())
+        }
+    }
+
+    fn assert_scalar<S: Scalar>(s: S) -> S {
+        s
+    }
+    assert!(assert_scalar(Wrapper(1.0)).is_finite());
+    assert!(Wrapper(f64::NAN).is_nan());
+    let spkrs = [
+        Speaker {
+            distance: Wrapper(1.0),
+            weight: Wrapper(1.0),
+        },
+        Speaker {
+            distance: Wrapper(2.0),
+            weight: Wrapper(1.0),
+        },
+    ];
+    assert!(SpeakerGains::new(&spkrs, Wrapper(6.0)).all(|g| g.is_finite()));
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn transform_2() {
+    use core::f64::consts::FRAC_PI_2;
+    let close = |[ax, ay]: [f64; 2], [bx, by]: [f64; 2]| {
+        assert!((ax - bx).abs() < 1e-12 && (ay - by).abs() < 1e-12);
+    };
+    let rotate = Transform2 {
+        rotation: FRAC_PI_2,
+        ..Transform2::identity()
+    };
+    close(rotate.apply([1.0, 0.0]), [0.0, 1.0]);
+    let translate = Transform2 {
+        translation: [2.0, -3.0],
+        ..Transform2::identity()
+    };
+    close(translate.apply([1.0, 1.0]), [3.0, -2.0]);
+    let p = [1.0, 0.0];
+    close(rotate.then(&translate).apply(p), [2.0, -2.0]);
+    close(translate.then(&rotate).apply(p), [3.0, 3.0]);
+    close(
+        rotate.then(&translate).apply(p),
+        translate.apply(rotate.apply(p)),
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_gains_contributions() {
+    let spkrs = [
+        Speaker::new(1.0, 1f64),
+        Speaker::new(2.0, 1f64),
+        Speaker::new(5.0, 1f64),
+    ];
+    let contributions = SpeakerGains::new(&spkrs, 6.0).contributions();
+    let total: f64 = contributions.iter().sum();
+    assert!((total - 1.0).abs() < 1e-12);
+    assert!(contributions[0] > contributions[1] && contributions[1] > contributions[2]);
+    let silent = [Speaker::new(1f64, 0.0)];
+    assert_eq!(SpeakerGains::new(&silent, 6.0).contributions(), vec![0.0]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn trail_buffer() {
+    let mut trail = TrailBuffer::new(3, 0.5);
+    assert_eq!(trail.update(&[1.0, 0.0, 0.0]), &[1.0, 0.0, 0.0]);
+    assert_eq!(trail.update(&[0.0, 1.0, 0.0]), &[0.5, 1.0, 0.0]);
+    assert_eq!(trail.update(&[0.0, 0.0, 1.0]), &[0.25, 0.5, 1.0]);
+    assert_eq!(trail.gains(), &[0.25, 0.5, 1.0]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn dbap_energy_invariant() {
+    let spkrs = [
+        Speaker::new(1f64, 1.0),
+        Speaker::new(3.0, 0.5),
+        Speaker::new(7.0, 2.0),
+    ];
+    for &r in &[3.0, 6.0, 9.0] {
+        let gains: Vec<_> = SpeakerGains::new(&spkrs, r).collect();
+        assert!((dbap_energy(&gains, &spkrs) - 1.0).abs() < 1e-12);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn occluded_gains() {
+    let mut spkrs = square_2::<f64>(10.0);
+    let (src, r, blur) = ([3.0, 4.0], 6.0, 0.1);
+    let mut occluded = [0.0; 4];
+    let occlusion = |_, i| if i == 2 { 0.0 } else { 1.0 };
+    occluded_gains_2(&spkrs, src, r, blur, occlusion, &mut occluded);
+    spkrs[2].weight = 0.0;
+    let muted: Vec<_> = SpeakerGains::new(&speakers_2(&spkrs, src, blur), r).collect();
+    assert_eq!(&occluded[..], &muted[..]);
+    assert_eq!(occluded[2], 0.0);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn fixed_field() {
+    let mut speakers = [Speaker::new(1f64, 1.0); 8];
+    for (i, s) in speakers.iter_mut().enumerate() {
+        s.distance = 1.0 + i as f64;
+        s.weight = 1.0 / (1.0 + i as f64 * 0.1);
+    }
+    let field = Field { speakers };
+    let gains: [f64; 8] = field.gains(6.0);
+    let expected: Vec<_> = SpeakerGains::new(&speakers, 6.0).collect();
+    assert_eq!(&gains[..], &expected[..]);
+}
+
+#[cfg(all(feature = "full", feature = "alloc"))]
+#[test]
+fn perceived_azimuth() {
+    use core::f64::consts::FRAC_PI_2;
+    let speaker = |position| PositionedSpeaker {
+        position,
+        weight: 1f64,
+    };
+    let spkrs = [
+        speaker([1.0, 0.0]),
+        speaker([0.0, 1.0]),
+        speaker([-1.0, 0.0]),
+        speaker([0.0, -1.0]),
+    ];
+    let (r, blur) = (6.0, 0.1);
+    let azimuth = |src| {
+        let gains: Vec<_> = SpeakerGains::new(&speakers_2(&spkrs, src, blur), r).collect();
+        perceived_azimuth_2(&spkrs, &gains, [0.0, 0.0])
+    };
+    assert!(azimuth([1.0, 0.0]).abs() < 1e-9);
+    assert!((azimuth([0.0, 1.0]) - FRAC_PI_2).abs() < 1e-9);
+    assert_eq!(perceived_azimuth_2(&spkrs, &[0.0; 4], [0.0, 0.0]), 0.0);
+}
+
+#[test]
+fn interpolate_normalized_gains() {
+    let mode = Normalization::ConstantPower;
+    let mut from = [1f64, 0.0, 0.0];
+    let mut to = [0.0, 0.2, 1.0];
+    normalize(&mut from, mode);
+    normalize(&mut to, mode);
+    let mut out = [0.0; 3];
+    interpolate_normalized(&from, &to, 0.5, mode, &mut out);
+    let power: f64 = out.iter().map(|g| g * g).sum();
+    assert!((power - 1.0).abs() < 1e-12);
+    interpolate_normalized(&from, &to, 0.5, Normalization::ConstantAmplitude, &mut out);
+    let sum: f64 = out.iter().sum();
+    assert!((sum - 1.0).abs() < 1e-12);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn max_distance_culling() {
+    let spkrs = [
+        Speaker::new(1.0, 1f64),
+        Speaker::new(2.0, 1f64),
+        Speaker::new(5.0, 1f64),
+        Speaker::new(8.0, 1f64),
+    ];
+    let mut out = [0.0; 4];
+    culled_gains(&spkrs, 6.0, 4.0, &mut out);
+    let expected: Vec<_> = SpeakerGains::new(&spkrs[..2], 6.0).collect();
+    assert_eq!(&out[..2], &expected[..]);
+    assert_eq!(&out[2..], &[0.0, 0.0]);
+
+    // All speakers beyond the radius falls back to the nearest.
+    culled_gains(&spkrs[2..], 6.0, 4.0, &mut out[..2]);
+    assert!(out[0] > 0.0);
+    assert_eq!(out[1], 0.0);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn line_source_gains() {
+    let spkrs = square_2::<f64>(10.0);
+    let (src, r, blur) = ([3.0, 4.0], 6.0, 0.1);
+    let mut out = [0.0; 4];
+    line_source_gains_2(&spkrs, src, src, 8, r, blur, &mut out);
+    let point: Vec<_> = SpeakerGains::new(&speakers_2(&spkrs, src, blur), r).collect();
+    for (o, p) in out.iter().zip(point) {
+        assert!((o - p).abs() < 1e-12);
+    }
+    // A symmetric segment produces a symmetric field.
+    line_source_gains_2(&spkrs, [0.0, 5.0], [10.0, 5.0], 5, r, blur, &mut out);
+    assert!((out[0] - out[1]).abs() < 1e-12);
+    assert!((out[0] - out[3]).abs() < 1e-12);
+}
+
+// Generates a test for each scenario that runs it over both `f32` and `f64`, asserting that the
+// results agree within `f32` precision.
+#[cfg(all(test, feature = "alloc"))]
+macro_rules! test_scalar_parity {
+    ($($name:ident => $scenario:ident;)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let a: Vec<f32> = $scenario::<f32>();
+                let b: Vec<f64> = $scenario::<f64>();
+                assert_eq!(a.len(), b.len());
+                for (&a, &b) in a.iter().zip(&b) {
+                    let tolerance = 1e-5 * b.abs().max(1.0);
+                    assert!((a as f64 - b).abs() < tolerance, "{} != {}", a, b);
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(all(test, feature = "alloc"))]
+fn parity_gains<S: Scalar>(positions: &[[f32; 2]], weights: &[f32], src: [f32; 2]) -> Vec<S> {
+    let spkrs: Vec<_> = positions
+        .iter()
+        .zip(weights)
+        .map(|(&[x, y], &w)| PositionedSpeaker {
+            position: [S::from_f32(x), S::from_f32(y)],
+            weight: S::from_f32(w),
+        })
+        .collect();
+    let src = [S::from_f32(src[0]), S::from_f32(src[1])];
+    let spkrs = speakers_2(&spkrs, src, S::from_f32(0.1));
+    SpeakerGains::new(&spkrs, S::from_f32(6.0)).collect()
+}
+
+#[cfg(all(test, feature = "alloc"))]
+fn parity_symmetric<S: Scalar>() -> Vec<S> {
+    let positions = [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]];
+    parity_gains(&positions, &[1.0; 4], [5.0, 5.0])
+}
+
+#[cfg(all(test, feature = "alloc"))]
+fn parity_asymmetric<S: Scalar>() -> Vec<S> {
+    let positions = [
+        [0.0, 0.0],
+        [7.5, 1.0],
+        [12.0, 9.0],
+        [-3.0, 6.0],
+        [4.0, 15.0],
+    ];
+    let weights = [1.0, 0.5, 2.0, 0.75, 1.25];
+    parity_gains(&positions, &weights, [2.5, 3.5])
+}
+
+#[cfg(all(test, feature = "alloc"))]
+test_scalar_parity! {
+    scalar_parity_symmetric => parity_symmetric;
+    scalar_parity_asymmetric => parity_asymmetric;
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn distance_buffer() {
+    let speaker = |position| PositionedSpeaker {
+        position,
+        weight: 1f64,
+    };
+    let spkrs = [
+        speaker([0.0, 0.0]),
+        speaker([10.0, 0.0]),
+        speaker([10.0, 10.0]),
+    ];
+    let blur = 0.1;
+    let mut buffer = DistanceBuffer::new(&spkrs, blur);
+    for &src in &[[1.0, 2.0], [8.0, 3.0], [5.0, 9.0]] {
+        buffer.update(src);
+        let fresh = speakers_2(&spkrs, src, blur);
+        for (a, b) in buffer.speakers().iter().zip(&fresh) {
+            assert_eq!(a.distance, b.distance);
+        }
+        let gains: Vec<_> = buffer.gains(6.0).collect();
+        let expected: Vec<_> = SpeakerGains::new(&fresh, 6.0).collect();
+        assert_eq!(gains, expected);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn relative_amplitudes() {
+    let spkrs = [
+        Speaker::new(1f64, 1.0),
+        Speaker::new(2.5, 0.5),
+        Speaker::new(4.0, 2.0),
+    ];
+    let gains = SpeakerGains::new(&spkrs, 6.0);
+    let amps: Vec<_> = gains.clone().relative_amplitudes().collect();
+    for ((amp, s), g) in amps.iter().zip(&spkrs).zip(gains) {
+        assert_eq!(amp / s.distance, g);
+    }
+    let a = a_coefficient(6.0);
+    let k = k_coefficient(a, &spkrs);
+    for (amp, s) in amps.iter().zip(&spkrs) {
+        assert_eq!(*amp, v_speaker_relative_amplitude(s, k, a));
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn speaker_hash_eq() {
+    use std::collections::HashMap;
+    let layout = |d| {
+        vec![
+            Speaker {
+                distance: d,
+                weight: 1i32,
+            },
+            Speaker {
+                distance: 2,
+                weight: 3,
+            },
+        ]
+    };
+    let mut memo = HashMap::new();
+    memo.insert(layout(1), "a");
+    memo.insert(layout(4), "b");
+    assert_eq!(memo.get(&layout(1)), Some(&"a"));
+    assert_eq!(memo.get(&layout(4)), Some(&"b"));
+    assert_eq!(memo.get(&layout(5)), None);
+}
+
+#[cfg(all(feature = "full", feature = "alloc"))]
+#[test]
+fn speaker_gains_to_dbfs() {
+    // A lone speaker with a weight and distance of `1.0` has unity gain.
+    let unity = [Speaker::new(1f64, 1.0)];
+    let db: Vec<_> = SpeakerGains::new(&unity, 6.0)
+        .to_dbfs(18.0, -96.0)
+        .collect();
+    assert!((db[0] + 18.0).abs() < 1e-9);
+    let silent = [Speaker::new(1f64, 1.0), Speaker::new(1.0, 0.0)];
+    let db: Vec<_> = SpeakerGains::new(&silent, 6.0)
+        .to_dbfs(18.0, -96.0)
+        .collect();
+    assert_eq!(db[1], -96.0);
+}
+
+#[test]
+fn k_coefficient_variants() {
+    let spkrs = [
+        Speaker::new(1f64, 1.0),
+        Speaker::new(2.5, 0.5),
+        Speaker::new(4.0, 2.0),
+    ];
+    let a = a_coefficient(6.0);
+    let sum: f64 = spkrs
+        .iter()
+        .map(|s| (s.weight * s.weight) / (s.distance * s.distance))
+        .sum();
+    assert_eq!(
+        k_coefficient_variant(a, &spkrs, KVariant::default()),
+        2.0 * a / sum
+    );
+    let k = k_coefficient(a, &spkrs);
+    assert_eq!(k_coefficient_variant(a, &spkrs, KVariant::MeanWeight), k);
+    // The two agree where the mean weight is `1.0`.
+    let unweighted = spkrs.map(|s| Speaker::new(s.distance, 1.0));
+    assert_eq!(
+        k_coefficient_variant(a, &unweighted, KVariant::Standard),
+        k_coefficient(a, &unweighted)
+    );
+    let k = k_coefficient_variant(a, &spkrs, KVariant::ConstantPower);
+    let power: f64 = spkrs
+        .iter()
+        .map(|s| v_speaker_relative_amplitude(s, k, a).powi(2))
+        .sum();
+    assert!((power - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn nearest_speaker_gains() {
+    let spkrs = square_2::<f64>(10.0);
+    let mut out = [0.0; 4];
+    nearest_speaker_gains_2(&spkrs, [8.0, 7.0], &mut out);
+    assert_eq!(out, [0.0, 0.0, 1.0, 0.0]);
+    nearest_speaker_gains_2(&spkrs, [1.0, 6.0], &mut out);
+    assert_eq!(out, [0.0, 0.0, 0.0, 1.0]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn flat_gains() {
+    let distances = [1.0, 2.5, 4.0, 7.0];
+    let weights = [1.0, 0.5, 2.0, 1.0];
+    let mut out = [0.0; 4];
+    flat_gains_f32(&distances, &weights, 6.0, &mut out);
+    let spkrs: Vec<_> = distances
+        .iter()
+        .zip(&weights)
+        .map(|(&distance, &weight)| Speaker::new(distance, weight))
+        .collect();
+    let expected: Vec<_> = SpeakerGains::new(&spkrs, 6.0).collect();
+    assert_eq!(&out[..], &expected[..]);
+}
+
+#[test]
+fn rolloff_ramp() {
+    let mut ramp = RolloffRamp::new(3f64);
+    assert_eq!(ramp.a(), a_coefficient(3.0));
+    ramp.ramp_to(9.0, 4);
+    let (start, end) = (a_coefficient(3.0), a_coefficient(9.0));
+    let mid = ramp.next_a();
+    assert!(mid < start && mid > end);
+    for _ in 0..3 {
+        ramp.next_a();
+    }
+    assert_eq!(ramp.a(), end);
+    assert_eq!(ramp.next_a(), end);
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn rolloff_ramp_gains() {
+    let spkrs = [
+        Speaker::new(1.0, 1f64),
+        Speaker::new(2.0, 1f64),
+        Speaker::new(4.0, 1f64),
+    ];
+    let modelled = |db| {
+        let mut out = [0.0; 3];
+        modelled_gains(&spkrs, &RolloffModel::Constant(db), &mut out);
+        out
+    };
+    let gains = |ramp: &RolloffRamp<f64>| {
+        let mut out = [0.0; 3];
+        ramp.gains(&spkrs, &mut out);
+        out
+    };
+    let mut ramp = RolloffRamp::new(3.0);
+    assert!(gains_approx_eq(&gains(&ramp), &modelled(3.0), 1e-12));
+    ramp.ramp_to(9.0, 4);
+    ramp.next_a();
+    let mid = gains(&ramp);
+    assert!(mid[2] < modelled(3.0)[2] && mid[2] > modelled(9.0)[2]);
+    for _ in 0..3 {
+        ramp.next_a();
+    }
+    assert!(gains_approx_eq(&gains(&ramp), &modelled(9.0), 1e-12));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn suggested_blur_scales_with_layout() {
+    let speaker = |position| PositionedSpeaker {
+        position,
+        weight: 1f64,
+    };
+    let spkrs = [
+        speaker([-1.0, 2.0]),
+        speaker([3.0, -1.0]),
+        speaker([2.0, 5.0]),
+    ];
+    assert_eq!(bounding_box_2(&spkrs), ([-1.0, -1.0], [3.0, 5.0]));
+    let scaled: Vec<_> = spkrs
+        .iter()
+        .map(|s| speaker([s.position[0] * 10.0, s.position[1] * 10.0]))
+        .collect();
+    let (blur, scaled_blur) = (suggested_blur(&spkrs), suggested_blur(&scaled));
+    assert!(blur > 0.0);
+    assert!((scaled_blur - blur * 10.0).abs() < 1e-12);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn dead_zone_gains() {
+    let speaker = |position| PositionedSpeaker {
+        position,
+        weight: 1f64,
+    };
+    let spkrs = [speaker([0.0, 0.0]), speaker([10.0, 0.0])];
+    let (r, blur, radius, transition) = (6.0, 0.1, 1.0, 2.0);
+    let mut out = [0.0; 2];
+    let dbap = |src| -> Vec<f64> { SpeakerGains::new(&speakers_2(&spkrs, src, blur), r).collect() };
+    // Inside the dead zone.
+    dead_zone_gains_2(&spkrs, [0.5, 0.0], r, blur, radius, transition, &mut out);
+    assert_eq!(out, [1.0, 0.0]);
+    // Halfway through the transition band.
+    let src = [2.0, 0.0];
+    dead_zone_gains_2(&spkrs, src, r, blur, radius, transition, &mut out);
+    let g = dbap(src);
+    assert!((out[0] - (0.5 + g[0] * 0.5)).abs() < 1e-12);
+    assert!((out[1] - g[1] * 0.5).abs() < 1e-12);
+    // Outside the dead zone and transition band.
+    let src = [5.0, 0.0];
+    dead_zone_gains_2(&spkrs, src, r, blur, radius, transition, &mut out);
+    assert_eq!(&out[..], &dbap(src)[..]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_scalar_conversions() {
+    let spkrs = [Speaker::new(1.5f32, 0.25), Speaker::new(3.0, 1.0)];
+    let f64s: Vec<Speaker<f64>> = convert_speakers(&spkrs);
+    assert_eq!(f64s[0].distance, 1.5);
+    assert_eq!(f64s[0].weight, 0.25);
+    let lossy = [Speaker::new(0.1f64, 1.0 / 3.0)];
+    let f32s: Vec<Speaker<f32>> = convert_speakers(&lossy);
+    assert_eq!(f32s[0].distance, 0.1f32);
+    assert_eq!(f32s[0].weight, 1.0 / 3.0f32);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn listener_speaker_vectors() {
+    let speaker = |position| PositionedSpeaker {
+        position,
+        weight: 1f64,
+    };
+    let spkrs = [
+        speaker([0.0, 0.0]),
+        speaker([10.0, 0.0]),
+        speaker([4.0, 7.0]),
+    ];
+    let listener = Listener {
+        position: [2.0, 3.0],
+    };
+    let vectors = listener.speaker_vectors(&spkrs);
+    assert_eq!(vectors, vec![[-2.0, -3.0], [8.0, -3.0], [2.0, 4.0]]);
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn quantize_fader_steps() {
+    let db = |db: f64| 10f64.powf(db / 20.0);
+    let mut gains = [1.0, db(-6.9), db(-2.9), db(-4.1), 0.0];
+    quantize_gains(&mut gains, 3.0);
+    let expected = [1.0, db(-6.0), db(-3.0), db(-3.0), 0.0];
+    for (g, e) in gains.iter().zip(&expected) {
+        assert!((g - e).abs() < 1e-12);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_gains_accumulate_into() {
+    let a = [
+        Speaker::new(1f64, 1.0),
+        Speaker::new(2.5, 0.5),
+        Speaker::new(4.0, 2.0),
+    ];
+    let b = [
+        Speaker::new(3f64, 1.0),
+        Speaker::new(1.5, 1.0),
+        Speaker::new(2.0, 0.5),
+    ];
+    let mut out = [0.0; 3];
+    SpeakerGains::new(&a, 6.0).accumulate_into(&mut out);
+    SpeakerGains::new(&b, 6.0).accumulate_into(&mut out);
+    let expected: Vec<_> = SpeakerGains::new(&a, 6.0)
+        .zip(SpeakerGains::new(&b, 6.0))
+        .map(|(a, b)| a + b)
+        .collect();
+    assert_eq!(&out[..], &expected[..]);
+}
+
+#[test]
+fn single_speaker() {
+    for &distance in &[0.5f64, 1.0, 10.0] {
+        for &r in &[3.0, 6.0, 9.0] {
+            let gain = |weight| {
+                let spkr = [Speaker::new(distance, weight)];
+                SpeakerGains::new(&spkr, r).next().unwrap()
+            };
+            assert!((gain(1.0) - 1.0).abs() < 1e-12);
+            assert!((gain(2.0) - 1.0).abs() < 1e-12);
+            assert_eq!(gain(0.0), 0.0);
+        }
+    }
+}
+
+#[cfg(all(feature = "full", feature = "alloc"))]
+#[test]
+fn rolloff_sweep() {
+    let speaker = |position| PositionedSpeaker {
+        position,
+        weight: 1f64,
+    };
+    let spkrs = [
+        speaker([0.0, 0.0]),
+        speaker([10.0, 0.0]),
+        speaker([4.0, 7.0]),
+    ];
+    let (src, blur) = ([2.0, 3.0], 0.1);
+    let rolloffs = [3.0, 4.5, 6.0, 7.5, 9.0];
+    let sweep = rolloff_sweep_2(&spkrs, src, blur, &rolloffs);
+    assert_eq!(sweep.len(), rolloffs.len());
+    let distances = speakers_2(&spkrs, src, blur);
+    for (row, &r) in sweep.iter().zip(&rolloffs) {
+        let mut expected = [0.0; 3];
+        modelled_gains(&distances, &RolloffModel::Constant(r), &mut expected);
+        assert_eq!(row, &expected);
+    }
+    // The nearest speaker takes a greater share of the gain as the rolloff rises.
+    for rows in sweep.windows(2) {
+        assert!(rows[1][0] > rows[0][0]);
+        assert!(rows[1][1] < rows[0][1]);
+    }
+}
+
+#[cfg(feature = "random-layouts")]
+#[test]
+fn random_layouts() {
+    let ring: Vec<PositionedSpeaker<f64>> = random_ring_2(16, 5.0, 42);
+    assert_eq!(ring.len(), 16);
+    assert_eq!(ring, random_ring_2(16, 5.0, 42));
+    assert_ne!(ring, random_ring_2(16, 5.0, 43));
+    for s in &ring {
+        let r = blurred_distance_2([0.0, 0.0], s.position, 0.0).sqrt();
+        assert!((r - 5.0).abs() < 1e-9);
+    }
+    let grid: Vec<PositionedSpeaker<f64>> = random_grid_2(4, 3, 10.0, 1.0, 7);
+    assert_eq!(grid.len(), 12);
+    assert_eq!(grid, random_grid_2(4, 3, 10.0, 1.0, 7));
+    for (i, s) in grid.iter().enumerate() {
+        let (col, row) = ((i % 4) as f64, (i / 4) as f64);
+        assert!((s.position[0] - col * 10.0).abs() <= 1.0);
+        assert!((s.position[1] - row * 10.0).abs() <= 1.0);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn loudness_compensation_map() {
+    let speaker = |position| PositionedSpeaker {
+        position,
+        weight: 1f64,
+    };
+    let spkrs = [
+        speaker([0.0, 0.0]),
+        speaker([2.0, 0.0]),
+        speaker([10.0, 0.0]),
+        speaker([10.0, 10.0]),
+        speaker([0.0, 10.0]),
+    ];
+    let (r, blur, res) = (6.0, 0.5, 8);
+    let map = loudness_compensation_map_2(&spkrs, r, blur, res);
+    let points = grid_points_2(&spkrs, res);
+    assert_eq!(map.len(), points.len());
+    let variance = |powers: &[f64]| {
+        let mean = powers.iter().sum::<f64>() / powers.len() as f64;
+        powers.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / powers.len() as f64
+    };
+    let (mut raw, mut compensated) = (vec![], vec![]);
+    for (&p, &c) in points.iter().zip(&map) {
+        let gains: Vec<_> = SpeakerGains::new(&speakers_2(&spkrs, p, blur), r).collect();
+        raw.push(total_power(&gains));
+        let gains: Vec<_> = gains.iter().map(|g| g * c).collect();
+        compensated.push(total_power(&gains));
+    }
+    assert!(variance(&compensated) < variance(&raw));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_gains_effective_distances() {
+    let spkrs = [
+        Speaker::new(0.01, 1f64),
+        Speaker::new(2.0, 1f64),
+        Speaker::new(5.0, 1f64),
+    ];
+    let gains = SpeakerGains::new(&spkrs, 6.0);
+    assert_eq!(gains.effective_distances(), vec![0.01, 2.0, 5.0]);
+    let clamped = gains.with_min_distance(1.0);
+    assert_eq!(clamped.effective_distances(), vec![1.0, 2.0, 5.0]);
+    let expected: Vec<_> = SpeakerGains::new(
+        &[
+            Speaker::new(1.0, 1f64),
+            Speaker::new(2.0, 1f64),
+            Speaker::new(5.0, 1f64),
+        ],
+        6.0,
+    )
+    .collect();
+    assert_eq!(clamped.collect::<Vec<_>>(), expected);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_gains_with_distance() {
+    let spkrs = [
+        Speaker::new(1f64, 1.0),
+        Speaker::new(2.5, 0.5),
+        Speaker::new(4.0, 2.0),
+    ];
+    let gains = SpeakerGains::new(&spkrs, 6.0);
+    let pairs: Vec<_> = gains.clone().with_distance().collect();
+    assert_eq!(pairs.len(), spkrs.len());
+    for ((&(d, g), s), gain) in pairs.iter().zip(&spkrs).zip(gains) {
+        assert_eq!(d, s.distance);
+        assert_eq!(g, gain);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn nearest_selector_hysteresis() {
+    let frames = [
+        [
+            Speaker::new(1.0, 1f64),
+            Speaker::new(1.01, 1f64),
+            Speaker::new(5.0, 1f64),
+        ],
+        [
+            Speaker::new(1.01, 1f64),
+            Speaker::new(1.0, 1f64),
+            Speaker::new(5.0, 1f64),
+        ],
+    ];
+    let mut no_hysteresis = NearestSelector::new(1, 0);
+    let mut hysteresis = NearestSelector::new(1, 1);
+    assert_eq!(no_hysteresis.select(&frames[0]), &[0]);
+    assert_eq!(hysteresis.select(&frames[0]), &[0]);
+    for _ in 0..4 {
+        for frame in &frames {
+            hysteresis.select(frame);
+            assert_eq!(hysteresis.selected(), &[0]);
+        }
+    }
+    assert_eq!(no_hysteresis.select(&frames[1]), &[1]);
+    // Once the speaker falls beyond the margin it is deselected.
+    let far = [
+        Speaker::new(5.0, 1f64),
+        Speaker::new(1.0, 1f64),
+        Speaker::new(1.5, 1f64),
+    ];
+    assert_eq!(hysteresis.select(&far), &[1]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_gains_debug_table() {
+    let spkrs = [Speaker::new(1f64, 1.0), Speaker::new(1.0, 0.0)];
+    let mut gains = SpeakerGains::new(&spkrs, 6.0);
+    gains.next();
+    let table = gains.debug_table();
+    let lines: Vec<_> = table.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "speaker | distance | weight | gain");
+    assert_eq!(lines[1], "      0 |        1 |      1 |    1");
+    assert_eq!(lines[2], "      1 |        1 |      0 |    0");
+}
+
+#[test]
+fn directive_speaker_weight() {
+    let speaker = |facing| DirectiveSpeaker {
+        position: [0.0, 0.0],
+        facing,
+        weight: 2f64,
+    };
+    let src = [0.0, 5.0];
+    let towards = speaker([0.0, 1.0]).speaker(src, 0.0);
+    let side = speaker([3.0, 0.0]).speaker(src, 0.0);
+    let away = speaker([0.0, -1.0]).speaker(src, 0.0);
+    assert!((towards.weight - 2.0).abs() < 1e-12);
+    assert!((side.weight - 1.0).abs() < 1e-12);
+    assert!(away.weight.abs() < 1e-12);
+    assert_eq!(towards.distance, 5.0);
+}
+
+#[test]
+fn gains_approx_equality() {
+    assert!(gains_approx_eq(
+        &[1.0, 0.5],
+        &[1.0 + 1e-9, 0.5 - 1e-9],
+        1e-6
+    ));
+    assert!(!gains_approx_eq(&[1.0, 0.5], &[1.0, 0.6], 1e-6));
+    assert!(!gains_approx_eq(&[1.0], &[1.0, 0.5], 1e-6));
+}
+
+#[cfg(feature = "approx")]
+#[test]
+fn speaker_approx_eq() {
+    use approx::{assert_abs_diff_eq, assert_relative_eq, assert_relative_ne};
+    let a = Speaker::new(1f64, 0.5);
+    let b = Speaker::new(1.0 + 1e-12, 0.5 - 1e-12);
+    assert_abs_diff_eq!(a, b, epsilon = 1e-9);
+    assert_relative_eq!(a, b, max_relative = 1e-9);
+    let c = Speaker { distance: 1.1, ..a };
+    assert_relative_ne!(a, c);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn normalization_pin_nearest() {
+    let spkrs = [
+        Speaker::new(3f64, 1.0),
+        Speaker::new(1.5, 0.25),
+        Speaker::new(2.0, 1.0),
+    ];
+    let gains = SpeakerGains::new(&spkrs, 6.0).normalized(Normalization::PinNearest(1.0));
+    assert!((gains[1] - 1.0).abs() < 1e-12);
+    let raw: Vec<_> = SpeakerGains::new(&spkrs, 6.0).collect();
+    assert!((gains[0] / gains[2] - raw[0] / raw[2]).abs() < 1e-12);
+    // Without distances, the loudest speaker is pinned.
+    let mut gains = raw.clone();
+    normalize(&mut gains, Normalization::PinNearest(0.5));
+    assert!((gains[2] - 0.5).abs() < 1e-12);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn incremental_field() {
+    let spkrs = [
+        Speaker::new(1f64, 1.0),
+        Speaker::new(2.5, 0.5),
+        Speaker::new(4.0, 2.0),
+        Speaker::new(3.0, 1.0),
+    ];
+    let mut field = IncrementalField::new(6.0);
+    assert_eq!(field.k_coefficient(), 0.0);
+    for (i, &s) in spkrs.iter().enumerate() {
+        field.push_speaker(s);
+        let batch = &spkrs[..=i];
+        let k = k_coefficient(a_coefficient(6.0), batch);
+        assert!((field.k_coefficient() - k).abs() < 1e-12);
+        let expected: Vec<_> = SpeakerGains::new(batch, 6.0).collect();
+        assert!(gains_approx_eq(
+            &field.gains().collect::<Vec<_>>(),
+            &expected,
+            1e-12
+        ));
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn limit_and_redistribute_gains() {
+    let mut gains = [0.9f64, 0.3, 0.2, 0.1];
+    let energy = total_power(&gains);
+    limit_and_redistribute(&mut gains, 0.6);
+    assert!(gains.iter().all(|&g| g <= 0.6 + 1e-12));
+    assert!((total_power(&gains) - energy).abs() < 1e-12);
+    assert!(gains[1] > gains[2] && gains[2] > gains[3]);
+    // Infeasible: all gains end at the threshold.
+    let mut gains = [1.0f64, 1.0];
+    limit_and_redistribute(&mut gains, 0.5);
+    assert_eq!(gains, [0.5, 0.5]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn limit_and_redistribute_soft_knee_gains() {
+    let (threshold, knee) = (0.6f64, 0.2);
+    let limited = |loudest| {
+        let mut gains = [loudest, 0.3, 0.2, 0.1];
+        limit_and_redistribute_soft_knee(&mut gains, threshold, knee);
+        gains
+    };
+    // Power is preserved as with a hard knee.
+    let energy = total_power(&[0.9, 0.3, 0.2, 0.1]);
+    assert!((total_power(&limited(0.9)) - energy).abs() < 1e-12);
+    // The gain reduction begins below the threshold, unlike a hard clamp.
+    assert_eq!(limited(0.5)[0], 0.5);
+    assert!(limited(threshold)[0] < threshold);
+    assert_eq!(limited(0.7)[0], threshold);
+    // Every output varies continuously as the loudest gain sweeps through the knee.
+    let step = 1e-4;
+    let mut prev = limited(0.4);
+    for i in 1..=4000 {
+        let gains = limited(0.4 + i as f64 * step);
+        for (g, p) in gains.iter().zip(&prev) {
+            assert!((g - p).abs() <= 2.0 * step);
+        }
+        assert!(gains.iter().all(|&g| g <= threshold));
+        prev = gains;
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn dominance_map() {
+    let spkrs = square_2::<f64>(10.0);
+    let map = dominance_map_2(&spkrs, 6.0, 0.1, 10);
+    assert_eq!(map.len(), 100);
+    let mut counts = [0; 4];
+    for &i in &map {
+        counts[i] += 1;
+    }
+    assert_eq!(counts, [25; 4]);
+    assert_eq!(map[0], 0);
+    assert_eq!(map[99], 2);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_gains_from_weight_distribution() {
+    let distances = [1f64, 2.0, 4.0];
+    let gains = SpeakerGains::from_weight_distribution(&distances, &[2.0, 1.0, 1.0], 6.0);
+    let spkrs = [
+        Speaker::new(1.0, 0.5),
+        Speaker::new(2.0, 0.25),
+        Speaker::new(4.0, 0.25),
+    ];
+    let expected: Vec<_> = SpeakerGains::new(&spkrs, 6.0).collect();
+    assert!(gains_approx_eq(&gains, &expected, 1e-12));
+}
+
+#[test]
+fn speaker_gains_degenerate() {
+    let silent = [Speaker::new(1f64, 0.0), Speaker::new(0.0, 1.0)];
+    assert!(SpeakerGains::new(&silent, 6.0).is_degenerate());
+    assert_eq!(
+        SpeakerGains::try_new(&silent, 6.0).err(),
+        Some(DegenerateField)
+    );
+    let quiet = [Speaker::new(1f64, 0.0), Speaker::new(100.0, 0.01)];
+    assert!(SpeakerGains::try_new(&quiet, 6.0).is_ok());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn merge_overlapping_layouts() {
+    let speaker = |position, weight| PositionedSpeaker { position, weight };
+    let a = [
+        speaker([5f64, 0.0], 1.0),
+        speaker([0.0, 5.0], 1.0),
+        speaker([-5.0, 0.0], 1.0),
+        speaker([0.0, -5.0], 1.0),
+    ];
+    let b = [
+        speaker([5.01, 0.0], 2.0),
+        speaker([3.5, 3.5], 1.0),
+        speaker([0.0, 4.99], 0.5),
+        speaker([-3.5, -3.5], 1.0),
+    ];
+    let merged = merge_layouts(&a, &b, 0.1);
+    assert_eq!(merged.len(), 6);
+    assert_eq!(merged[0], speaker([5.0, 0.0], 2.0));
+    assert_eq!(merged[1], speaker([0.0, 5.0], 1.0));
+    assert_eq!(&merged[4..], &[b[1], b[3]]);
+    assert_eq!(merge_layouts(&a, &b, 0.0).len(), 8);
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn rolloff_model_knee() {
+    let spkrs = [
+        Speaker::new(1.0, 1f64),
+        Speaker::new(2.0, 1f64),
+        Speaker::new(4.0, 1f64),
+    ];
+    let mut out = [0.0; 3];
+    modelled_gains(&spkrs, &RolloffModel::Constant(6.0), &mut out);
+    let db = |g: f64| 20.0 * g.log10();
+    assert!((db(out[0]) - db(out[1]) - 6.0).abs() < 1e-12);
+    assert!((db(out[1]) - db(out[2]) - 6.0).abs() < 1e-12);
+    assert!((dbap_energy(&out, &spkrs) - 1.0).abs() < 1e-12);
+
+    let knee = |far_db| RolloffModel::Knee {
+        near_db: 3.0,
+        far_db,
+        knee_distance: 2.0,
+    };
+    assert_eq!(knee(9.0).rolloff_db(2.0), 6.0);
+    let gains_at = |d, far_db| {
+        let spkrs = [Speaker::new(d, 1f64), Speaker::new(5.0, 1f64)];
+        let mut out = [0.0; 2];
+        modelled_gains(&spkrs, &knee(far_db), &mut out);
+        out
+    };
+    let eps = 1e-6;
+    assert!((gains_at(2.0 - eps, 9.0)[0] - gains_at(2.0 + eps, 9.0)[0]).abs() < 1e-5);
+    assert!((gains_at(2.0, 9.0)[0] - gains_at(2.0 + eps, 9.0)[0]).abs() < 1e-5);
+
+    // Raising the far-field rolloff lowers the share of the far speaker.
+    let share = |far_db| {
+        let [near, far] = gains_at(1.0, far_db);
+        far / near
+    };
+    assert!(share(3.0) > share(9.0));
+    assert!(share(9.0) > share(12.0));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn dbap_builder_gains_at() {
+    let spkrs = square_2::<f64>(10.0);
+    let src = [2.0, 3.0];
+    let gains = DbapBuilder::new()
+        .rolloff_db(4.5)
+        .blur(0.2)
+        .gains_at_2(&spkrs, src);
+    let expected: Vec<_> = SpeakerGains::new(&speakers_2(&spkrs, src, 0.2), 4.5).collect();
+    assert_eq!(gains, expected);
+    let nearest = DbapBuilder::new().nearest(1).gains_at_2(&spkrs, src);
+    assert_eq!(nearest[1..], [0.0; 3]);
+    assert!(nearest[0] > 0.0);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn wrapped_distance() {
+    let c = 10f64;
+    assert!((wrapped_distance_1(9.9, 0.5, c, 0.0) - 0.36).abs() < 1e-12);
+    assert!((wrapped_distance_1(0.5, 9.9, c, 0.0) - 0.36).abs() < 1e-12);
+    assert!((wrapped_distance_1(2.0, 5.0, c, 0.0) - 9.0).abs() < 1e-12);
+    let spkrs: Vec<_> = [0.5, 5.0, 7.0]
+        .iter()
+        .map(|&p| Speaker::new(wrapped_distance_1(9.9, p, c, 0.1).sqrt(), 1.0))
+        .collect();
+    let gains: Vec<_> = SpeakerGains::new(&spkrs, 6.0).collect();
+    assert!(gains[0] > gains[2] && gains[2] > gains[1]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_gains_with_delays() {
+    let spkrs = [
+        Speaker::new(1.0, 1f64),
+        Speaker::new(2.0, 1f64),
+        Speaker::new(4.0, 1f64),
+    ];
+    let gains = SpeakerGains::new(&spkrs, 6.0);
+    let pairs: Vec<_> = gains.clone().with_delays(343.0, 48_000.0).collect();
+    let per_unit = 48_000.0 / 343.0;
+    for ((&(g, delay), s), gain) in pairs.iter().zip(&spkrs).zip(gains) {
+        assert_eq!(g, gain);
+        assert!((delay - s.distance * per_unit).abs() < 1e-9);
+    }
+    assert!((pairs[2].1 - pairs[1].1 * 2.0).abs() < 1e-9);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_gains_weight_scale_invariance() {
+    let spkrs = [
+        Speaker::new(1f64, 1.0),
+        Speaker::new(2.5, 0.5),
+        Speaker::new(4.0, 2.0),
+    ];
+    let gains: Vec<_> = SpeakerGains::new(&spkrs, 6.0).collect();
+    for &scale in &[0.25, 2.0, 100.0] {
+        let scaled: Vec<_> = spkrs
+            .iter()
+            .map(|s| Speaker::new(s.distance, s.weight * scale))
+            .collect();
+        let scaled: Vec<_> = SpeakerGains::new(&scaled, 6.0).collect();
+        assert!(gains_approx_eq(&gains, &scaled, 1e-12));
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn zone_crossfade() {
+    let spkr = |x, y| PositionedSpeaker {
+        position: [x, y],
+        weight: 1.0,
+    };
+    let speakers = [
+        spkr(0.0, 0.0),
+        spkr(0.0, 2.0),
+        spkr(4.0, 0.0),
+        spkr(4.0, 2.0),
+    ];
+    let zone = |x, speakers| Zone {
+        min: [x, 0.0],
+        max: [x + 2.0, 2.0],
+        speakers,
+    };
+    let zones = Zones {
+        zones: vec![zone(0.0, vec![0, 1]), zone(2.0, vec![2, 3])],
+        crossfade: 1.0,
+    };
+    let mut gains = [0.0f32; 4];
+    // Deep within the left zone, only its speakers receive gain.
+    zone_gains_2(&speakers, &zones, [0.5, 1.0], 6.0, 0.0, &mut gains);
+    assert_eq!(&gains[2..], &[0.0, 0.0]);
+    assert!(gains[0] > 0.0);
+    assert_eq!(gains[0], gains[1]);
+    // On the boundary, both zones are equally active.
+    assert_eq!(zones.activations([2.0, 1.0]), vec![0.5, 0.5]);
+    zone_gains_2(&speakers, &zones, [2.0, 1.0], 6.0, 0.0, &mut gains);
+    assert!((gains[0] - gains[2]).abs() < 1e-6);
+    // The crossfade is continuous as the source crosses the boundary.
+    let mut prev = [0.0; 4];
+    zone_gains_2(&speakers, &zones, [1.0, 1.0], 6.0, 0.0, &mut prev);
+    for i in 1..=100 {
+        let x = 1.0 + i as f32 * 0.02;
+        zone_gains_2(&speakers, &zones, [x, 1.0], 6.0, 0.0, &mut gains);
+        assert!(gains_approx_eq(&gains, &prev, 0.05));
+        prev = gains;
+    }
+    assert_eq!(&gains[..2], &[0.0, 0.0]);
+    // An empty zone overlapping the left zone is silent.
+    let mut zones = zones;
+    zones.zones.push(zone(0.0, vec![]));
+    zone_gains_2(&speakers, &zones, [0.5, 1.0], 6.0, 0.0, &mut gains);
+    zones.zones.pop();
+    let mut full = [0.0f32; 4];
+    zone_gains_2(&speakers, &zones, [0.5, 1.0], 6.0, 0.0, &mut full);
+    for (g, f) in gains.iter().zip(&full) {
+        assert!((g * 2.0 - f).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn layout_conditioning() {
+    let spkr = |x, y| PositionedSpeaker {
+        position: [x, y],
+        weight: 1.0,
+    };
+    let square = [
+        spkr(0.0, 0.0),
+        spkr(0.0, 1.0),
+        spkr(1.0, 0.0),
+        spkr(1.0, 1.0),
+    ];
+    let nearly_collinear = [
+        spkr(0.0, 0.0),
+        spkr(1.0, 0.01),
+        spkr(2.0, 0.0),
+        spkr(3.0, 0.01),
+    ];
+    let square_cond = layout_conditioning_2(&square);
+    assert!((square_cond - 1.0f64).abs() < 1e-9);
+    assert!(layout_conditioning_2(&nearly_collinear) > 1000.0 * square_cond);
+    let collinear = [spkr(0.0, 0.0), spkr(1.0, 0.0), spkr(2.0, 0.0)];
+    assert_eq!(layout_conditioning_2(&collinear), f64::INFINITY);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn gains_over_path() {
+    let speakers: [PositionedSpeaker<f32>; 4] = square_2(10.0);
+    let path: Vec<[f32; 2]> = (0..10).map(|i| [i as f32, 10.0 - i as f32 * 0.5]).collect();
+    let gains: Vec<Vec<f32>> =
+        gains_over_path_2(&speakers, path.iter().cloned(), 6.0, 0.5).collect();
+    assert_eq!(gains.len(), path.len());
+    for (g, &p) in gains.iter().zip(&path) {
+        let spkrs = speakers_2(&speakers, p, 0.5);
+        let expected: Vec<f32> = SpeakerGains::new(&spkrs, 6.0).collect();
+        assert_eq!(g, &expected);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn weight_ramp() {
+    let ramp = WeightRamp {
+        engage_distance: 2.0,
+        ramp_width: 4.0,
+    };
+    assert_eq!(ramp.apply(Speaker::new(1.0, 0.5)).weight, 0.5);
+    assert_eq!(ramp.apply(Speaker::new(2.0, 0.5)).weight, 0.5);
+    assert_eq!(ramp.apply(Speaker::new(3.0, 0.5)).weight, 0.375);
+    assert_eq!(ramp.apply(Speaker::new(4.0, 0.5)).weight, 0.25);
+    assert_eq!(ramp.apply(Speaker::new(6.0, 0.5)).weight, 0.0);
+    assert_eq!(ramp.apply(Speaker::new(9.0, 0.5)).weight, 0.0);
+    let mut prev = 0.5;
+    for i in 0..=100 {
+        let w = ramp.apply(Speaker::new(i as f64 * 0.1, 0.5)).weight;
+        assert!(w <= prev);
+        prev = w;
+    }
+    let speakers = [Speaker::new(1.0, 0.5), Speaker::new(8.0, 0.5)];
+    let mut gains = [0.0; 2];
+    ramped_gains(&speakers, &[ramp, ramp], 6.0, &mut gains);
+    assert_eq!(gains, [1.0, 0.0]);
+}
+
+#[cfg(all(feature = "full", feature = "alloc"))]
+#[test]
+fn max_rolloff_for_floor() {
+    let speakers: [PositionedSpeaker<f64>; 4] = square_2(10.0);
+    let source = [2.0, 3.0];
+    let spkrs = speakers_2(&speakers, source, 0.1);
+    let min_gain = |rolloff_db| {
+        let mut gains = [0.0; 4];
+        modelled_gains(&spkrs, &RolloffModel::Constant(rolloff_db), &mut gains);
+        gains.iter().cloned().fold(f64::INFINITY, f64::min)
+    };
+    let floor = min_gain(6.0);
+    let rolloff = max_rolloff_for_floor_2(&speakers, source, 0.1, floor);
+    assert!((rolloff - 6.0).abs() < 1e-6);
+    assert!(min_gain(rolloff) >= floor);
+    assert!(min_gain(rolloff + 1e-6) < floor);
+    // A floor above the gains without rolloff can never be met.
+    assert_eq!(max_rolloff_for_floor_2(&speakers, source, 0.1, 0.3), 0.0);
+}
+
+#[test]
+fn field_into_iter() {
+    let spkr = |weight| Speaker::new(1.0, weight);
+    let field = Field {
+        speakers: [spkr(0.25), spkr(0.5), spkr(1.0)],
+    };
+    let mut sum = 0.0;
+    for speaker in &field {
+        sum += speaker.weight;
+    }
+    assert_eq!(sum, 1.75);
+    assert_eq!((&field).into_iter().count(), 3);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_gains_with_master() {
+    let speakers: Vec<Speaker> = [1.0, 2.0, 4.0]
+        .iter()
+        .map(|&distance| Speaker::new(distance, 1.0))
+        .collect();
+    let gains: Vec<f32> = SpeakerGains::new(&speakers, 6.0).collect();
+    let trimmed: Vec<f32> = SpeakerGains::new(&speakers, 6.0)
+        .with_master(0.5, false)
+        .collect();
+    for (g, t) in gains.iter().zip(&trimmed) {
+        assert_eq!(g * 0.5, *t);
+    }
+    let muted: Vec<f32> = SpeakerGains::new(&speakers, 6.0)
+        .with_master(0.5, true)
+        .collect();
+    assert_eq!(muted, vec![0.0; 3]);
+    let normalized = SpeakerGains::new(&speakers, 6.0)
+        .with_master(0.5, false)
+        .normalized(Normalization::ConstantAmplitude);
+    assert!((normalized.iter().sum::<f32>() - 0.5).abs() < 1e-6);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn underutilized_speakers() {
+    let speakers: Vec<PositionedSpeaker> = [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [500.0, 500.0]]
+        .iter()
+        .map(|&position| PositionedSpeaker {
+            position,
+            weight: 1.0,
+        })
+        .collect();
+    let path = (0..20).map(|i| [i as f32 * 0.5, 5.0]);
+    let unused = underutilized_speakers_2(&speakers, path.clone(), 6.0, 0.1, 0.05);
+    assert_eq!(unused, vec![3]);
+    assert!(underutilized_speakers_2(&speakers, path, 6.0, 0.1, 0.0).is_empty());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn blend_dbap_vbap() {
+    let speakers: [PositionedSpeaker<f64>; 4] = square_2(10.0);
+    let source = [3.0, 1.0];
+    let mut gains = [0.0; 4];
+    blend_dbap_vbap_2(&speakers, source, 1.0, 6.0, 0.0, &mut gains);
+    let spkrs = speakers_2(&speakers, source, 0.0);
+    let expected: Vec<f64> = SpeakerGains::new(&spkrs, 6.0).collect();
+    assert!(gains_approx_eq(&gains, &expected, 1e-12));
+    blend_dbap_vbap_2(&speakers, source, 0.0, 6.0, 0.0, &mut gains);
+    assert!(gains[0] > gains[1]);
+    assert_eq!(&gains[2..], &[0.0, 0.0]);
+    assert!((total_power(&gains) - 1.0).abs() < 1e-12);
+    let lone = [speakers[0]];
+    let mut gain = [0.0];
+    nearest_pair_gains_2(&lone, source, 0.0, &mut gain);
+    assert_eq!(gain, [1.0]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_calibration() {
+    let spkrs = [
+        Speaker::new(1.0, 1.0),
+        Speaker::new(2.0, 1.0),
+        Speaker::new(4.0, 1.0),
+    ];
+    let calibration_db = [-6.0, 0.0, 20.0];
+    let factor = |db: f64| 10f64.powf(db / 20.0);
+    let gains: Vec<f64> = SpeakerGains::new(&spkrs, 6.0).collect();
+    let trimmed: Vec<f64> = SpeakerGains::new(&spkrs, 6.0)
+        .with_calibration(&calibration_db)
+        .collect();
+    assert_eq!(trimmed[1], gains[1]);
+    assert!((trimmed[0] - gains[0] * factor(-6.0)).abs() < 1e-12);
+    assert!((trimmed[2] - gains[2] * 10.0).abs() < 1e-12);
+    // The relative amplitudes exclude the calibration, which each gain then applies.
+    let gains = SpeakerGains::new(&spkrs, 6.0)
+        .with_calibration(&calibration_db)
+        .with_master(0.5, false);
+    let amps: Vec<f64> = gains.clone().relative_amplitudes().collect();
+    let trims = spkrs.iter().zip(&calibration_db);
+    for ((amp, (s, &db)), g) in amps.iter().zip(trims).zip(gains.clone()) {
+        assert!((amp / s.distance * factor(db) * 0.5 - g).abs() < 1e-12);
+    }
+    // Normalisation leaves the calibration trims intact.
+    for mode in [Normalization::ConstantPower, Normalization::PinNearest(1.0)] {
+        let normalized = gains.clone().normalized(mode);
+        let reference = SpeakerGains::new(&spkrs, 6.0).normalized(mode);
+        for ((n, r), &db) in normalized.iter().zip(&reference).zip(&calibration_db) {
+            assert!((n - r * factor(db) * 0.5).abs() < 1e-12);
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn most_uniform_source() {
+    let speakers: [PositionedSpeaker<f32>; 4] = square_2(10.0);
+    let [x, y] = most_uniform_source_2(&speakers, 6.0, 0.1, 9);
+    assert!((x - 5.0).abs() < 1e-4 && (y - 5.0).abs() < 1e-4);
+    let [x, y] = most_uniform_source_2(&speakers, 6.0, 0.1, 8);
+    assert!((x - 5.0).abs() <= 0.625 && (y - 5.0).abs() <= 0.625);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn capped_speaker_gains() {
+    let speakers = [
+        Speaker::new(1.0, 1.0),
+        Speaker::new(2.0, 1.0),
+        Speaker::new(4.0, 1.0),
+        Speaker::new(8.0, 1.0),
+    ];
+    let uncapped: Vec<f64> = SpeakerGains::new(&speakers, 6.0).collect();
+    let caps = [0.5, 1.0, 1.0, 1.0];
+    let mut gains = [0.0; 4];
+    capped_gains(&speakers, &caps, 6.0, &mut gains);
+    assert_eq!(gains[0], 0.5);
+    for i in 1..4 {
+        assert!(gains[i] > uncapped[i]);
+    }
+    // The nearest neighbour with headroom takes on the most of the excess.
+    assert!(gains[1] - uncapped[1] > gains[3] - uncapped[3]);
+    assert!((total_power(&gains) - total_power(&uncapped)).abs() < 1e-12);
+    // Caps that are never reached leave the gains untouched.
+    capped_gains(&speakers, &[1.0; 4], 6.0, &mut gains);
+    assert_eq!(&gains[..], &uncapped[..]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_gains_reset() {
+    let speakers = [
+        Speaker::new(1.0, 1.0),
+        Speaker::new(2.0, 1.0),
+        Speaker::new(3.0, 1.0),
+        Speaker::new(4.0, 1.0),
+    ];
+    let expected: Vec<f32> = SpeakerGains::new(&speakers, 6.0).collect();
+    let mut gains = SpeakerGains::new(&speakers, 6.0);
+    assert_eq!(gains.next(), Some(expected[0]));
+    assert_eq!(gains.next(), Some(expected[1]));
+    gains.reset();
+    assert_eq!(gains.collect::<Vec<_>>(), expected);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn interpolate_virtual_gains() {
+    let layout = |positions: &[[f64; 2]]| -> Vec<PositionedSpeaker<f64>> {
+        positions
+            .iter()
+            .map(|&position| PositionedSpeaker {
+                position,
+                weight: 1.0,
+            })
+            .collect()
+    };
+    let physical: [PositionedSpeaker<f64>; 4] = square_2(10.0);
+    let source = [3.0, 4.0];
+    let mut gains = [0.0; 4];
+    interpolate_virtual_gains_2(&physical, &physical, source, 6.0, 0.1, &mut gains);
+    let spkrs = speakers_2(&physical, source, 0.1);
+    let direct: Vec<f64> = SpeakerGains::new(&spkrs, 6.0).collect();
+    assert_eq!(&gains[..], &direct[..]);
+    // A denser virtual grid still favours the physical speaker nearest to the source.
+    let grid = layout(&grid_points_2(&physical, 4));
+    interpolate_virtual_gains_2(&physical, &grid, source, 6.0, 0.1, &mut gains);
+    assert!(gains.iter().all(|&g| g > 0.0));
+    assert!(gains[0] > gains[1] && gains[0] > gains[2] && gains[0] > gains[3]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn gain_locks() {
+    let speakers = [
+        Speaker::new(1.0, 1.0),
+        Speaker::new(2.0, 1.0),
+        Speaker::new(4.0, 1.0),
+        Speaker::new(8.0, 1.0),
+    ];
+    let unlocked: Vec<f64> = SpeakerGains::new(&speakers, 6.0).collect();
+    let mut gains = [0.0; 4];
+    locked_gains(&speakers, &[(2, 0.5)], 6.0, &mut gains);
+    assert_eq!(gains[2], 0.5);
+    assert!((total_power(&gains) - total_power(&unlocked)).abs() < 1e-12);
+    // The remaining speakers retain the DBAP distribution computed without the locked speaker.
+    let others = [speakers[0], speakers[1], speakers[3]];
+    let expected: Vec<f64> = SpeakerGains::new(&others, 6.0).collect();
+    let ratio = gains[0] / expected[0];
+    assert!((gains[1] / expected[1] - ratio).abs() < 1e-12);
+    assert!((gains[3] / expected[2] - ratio).abs() < 1e-12);
+    // Locks exceeding the power budget silence the remaining speakers.
+    locked_gains(&speakers, &[(0, 2.0)], 6.0, &mut gains);
+    assert_eq!(gains, [2.0, 0.0, 0.0, 0.0]);
+    // Unlocked speakers that are all muted are silenced rather than left untouched.
+    let mut muted = speakers;
+    muted[1].weight = 0.0;
+    muted[2].weight = 0.0;
+    let mut gains = [9.0; 4];
+    locked_gains(&muted, &[(0, 0.1), (3, 0.1)], 6.0, &mut gains);
+    assert_eq!(gains, [0.1, 0.0, 0.0, 0.1]);
+}
+
+// A double-double scalar with roughly twice the precision of `f64`, represented as the unevaluated
+// sum of a high and low `f64`.
+#[cfg(all(test, feature = "alloc"))]
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+struct DoubleDouble(f64, f64);
+
+#[cfg(all(test, feature = "alloc"))]
+impl DoubleDouble {
+    const LN_2: Self = DoubleDouble(core::f64::consts::LN_2, 2.319_046_813_846_299_6e-17);
+
+    fn two_sum(a: f64, b: f64) -> Self {
+        let s = a + b;
+        let bb = s - a;
+        DoubleDouble(s, (a - (s - bb)) + (b - bb))
+    }
+
+    fn quick_two_sum(a: f64, b: f64) -> Self {
+        let s = a + b;
+        DoubleDouble(s, b - (s - a))
+    }
+
+    fn scale(self, factor: f64) -> Self {
+        DoubleDouble(self.0 * factor, self.1 * factor)
+    }
+
+    fn exp(self) -> Self {
+        let k = (self.0 / Self::LN_2.0).round();
+        let r = (self - Self::LN_2 * DoubleDouble(k, 0.0)).scale(1.0 / 1024.0);
+        let one = DoubleDouble(1.0, 0.0);
+        let (mut sum, mut term) = (one, one);
+        for n in 1..30 {
+            term = term * r / DoubleDouble(n as f64, 0.0);
+            sum = sum + term;
+        }
+        for _ in 0..10 {
+            sum = sum * sum;
+        }
+        sum.scale(2f64.powi(k as i32))
+    }
+
+    fn ln(self) -> Self {
+        let one = DoubleDouble(1.0, 0.0);
+        let mut y = DoubleDouble(self.0.ln(), 0.0);
+        for _ in 0..2 {
+            y = y + self * (-y).exp() - one;
+        }
+        y
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+impl Scalar for DoubleDouble {
+    fn from_f32(f: f32) -> Self {
+        DoubleDouble(f.into(), 0.0)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+impl Add for DoubleDouble {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        let s = Self::two_sum(self.0, rhs.0);
+        let t = Self::two_sum(self.1, rhs.1);
+        let s = Self::quick_two_sum(s.0, s.1 + t.0);
+        Self::quick_two_sum(s.0, s.1 + t.1)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+impl Div for DoubleDouble {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self {
+        let q1 = self.0 / rhs.0;
+        let r = self - rhs * DoubleDouble(q1, 0.0);
+        let q2 = r.0 / rhs.0;
+        let r = r - rhs * DoubleDouble(q2, 0.0);
+        let q3 = r.0 / rhs.0;
+        Self::quick_two_sum(q1, q2) + DoubleDouble(q3, 0.0)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+impl Mul for DoubleDouble {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        let p = self.0 * rhs.0;
+        let e = self.0.mul_add(rhs.0, -p) + (self.0 * rhs.1 + self.1 * rhs.0);
+        Self::quick_two_sum(p, e)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+impl Neg for DoubleDouble {
+    type Output = Self;
+    fn neg(self) -> Self {
+        DoubleDouble(-self.0, -self.1)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+impl Pow<DoubleDouble> for DoubleDouble {
+    type Output = Self;
+    fn pow(self, rhs: Self) -> Self {
+        match self.0 == 0.0 {
+            true => DoubleDouble(0.0, 0.0),
+            false => (rhs * self.ln()).exp(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+impl Sub for DoubleDouble {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        self + -rhs
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+impl Sum for DoubleDouble {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(DoubleDouble(0.0, 0.0), |a, b| a + b)
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn high_precision_scalar() {
+    // Sanity check the double-double arithmetic against the known digits of √2.
+    let sqrt_2 = DoubleDouble(2.0, 0.0).pow(DoubleDouble(0.5, 0.0));
+    assert_eq!(sqrt_2.0, core::f64::consts::SQRT_2);
+    assert!((sqrt_2.1 - -9.667_293_313_452_913e-17).abs() < 1e-29);
+    // The `f64` gains agree with the high-precision gains to within the precision of `f64`.
+    let positions = [
+        [0.0, 0.0],
+        [10.0, 0.0],
+        [10.0, 10.0],
+        [0.0, 10.0],
+        [3.0, 7.0],
+    ];
+    let weights = [1.0, 0.5, 1.0, 0.25, 0.75];
+    let high = parity_gains::<DoubleDouble>(&positions, &weights, [2.0, 3.0]);
+    let low = parity_gains::<f64>(&positions, &weights, [2.0, 3.0]);
+    for (h, &l) in high.iter().zip(&low) {
+        let error = (*h - DoubleDouble(l, 0.0)) / *h;
+        assert!(error.0.abs() < 8.0 * f64::EPSILON);
+        assert!(h.1 != 0.0);
+    }
+}
+
+#[cfg(all(feature = "full", feature = "alloc"))]
+#[test]
+fn total_power_drolloff() {
+    let speakers: Vec<PositionedSpeaker<f64>> = [[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 7.0]]
+        .iter()
+        .map(|&position| PositionedSpeaker {
+            position,
+            weight: 1.0,
+        })
+        .collect();
+    let source = [2.0, 3.0];
+    let spkrs = speakers_2(&speakers, source, 0.1);
+    let power = |rolloff_db| {
+        let mut gains = [0.0; 4];
+        modelled_gains(&spkrs, &RolloffModel::Constant(rolloff_db), &mut gains);
+        total_power(&gains)
+    };
+    for &rolloff_db in &[3.0, 6.0, 12.0] {
+        let h = 1e-3;
+        let finite_difference = (power(rolloff_db + h) - power(rolloff_db - h)) / (2.0 * h);
+        let analytic = total_power_drolloff_2(&speakers, source, 0.1, rolloff_db);
+        // Rolloff concentrates the gain towards the nearest speaker, raising the total power.
+        assert!(analytic > 0.0);
+        assert!((finite_difference - analytic).abs() < 1e-6 * analytic);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_names() {
+    let speaker = Speaker::new(1.0f64, 1.0);
+    let spkrs = [speaker, speaker];
+    // Names loaded at runtime, e.g. from a configuration file.
+    let loaded = [String::from("left"), String::from("right")];
+    let names: Vec<&str> = loaded.iter().map(String::as_str).collect();
+    let table = SpeakerGains::new(&spkrs, 6.0).debug_table_with_names(&names);
+    let lines: Vec<_> = table.lines().collect();
+    assert_eq!(lines[1], "   left |        1 |      1 |  0.5");
+    assert_eq!(lines[2], "  right |        1 |      1 |  0.5");
+    let table = SpeakerGains::new(&spkrs, 6.0).debug_table_with_names(&[]);
+    assert_eq!(table, SpeakerGains::new(&spkrs, 6.0).debug_table());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn response_curves() {
+    let speakers = [
+        Speaker::new(1.0, 1.0),
+        Speaker::new(3.0, 1.0),
+        Speaker::new(7.0, 1.0),
+        Speaker::new(9.0, 1.0),
+    ];
+    let expected: Vec<f64> = SpeakerGains::new(&speakers, 6.0).collect();
+    let mut gains = [0.0; 4];
+    curved_gains(&speakers, &Identity, 6.0, &mut gains);
+    assert_eq!(&gains[..], &expected[..]);
+    let linear = CubicBezier {
+        c1: 1.0 / 3.0,
+        c2: 2.0 / 3.0,
+    };
+    curved_gains(&speakers, &linear, 6.0, &mut gains);
+    assert!(gains_approx_eq(&gains, &expected, 1e-12));
+    // Steepening the curve sharpens localisation towards the nearest speaker.
+    let share = |gains: &[f64]| gains[0] / gains.iter().sum::<f64>();
+    curved_gains(&speakers, &|x: f64| x * x, 6.0, &mut gains);
+    assert!(share(&gains) > share(&expected));
+    let steep = CubicBezier { c1: 0.0, c2: 0.2 };
+    curved_gains(&speakers, &steep, 6.0, &mut gains);
+    assert!(share(&gains) > share(&expected));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn paired_speaker_gains() {
+    let spkr = |x, y| PositionedSpeaker {
+        position: [x, y],
+        weight: 1.0f64,
+    };
+    let speakers = [
+        spkr(0.0, 0.0),
+        spkr(2.0, 0.0),
+        spkr(0.0, 10.0),
+        spkr(10.0, 10.0),
+    ];
+    let pairs = [SpeakerPair {
+        left: 0,
+        right: 1,
+        weight: 1.0,
+    }];
+    let source = [0.5, 1.0];
+    let mut entity = [0.0; 4];
+    let mut members = [0.0; 4];
+    paired_gains_2(
+        &speakers,
+        &pairs,
+        PairCounting::Entity,
+        source,
+        6.0,
+        0.0,
+        &mut entity,
+    );
+    paired_gains_2(
+        &speakers,
+        &pairs,
+        PairCounting::Members,
+        source,
+        6.0,
+        0.0,
+        &mut members,
+    );
+    // Both members of a pair receive the same gain, despite the source being nearer to the left.
+    assert_eq!(entity[0], entity[1]);
+    assert_eq!(members[0], members[1]);
+    // Counted as members, the pair behaves as two independent speakers at its centre.
+    let centred = [spkr(1.0, 0.0), spkr(1.0, 0.0), speakers[2], speakers[3]];
+    let spkrs = speakers_2(&centred, source, 0.0);
+    let expected: Vec<f64> = SpeakerGains::new(&spkrs, 6.0).collect();
+    assert!(gains_approx_eq(&members, &expected, 1e-12));
+    // Counted as an entity, the whole field is louder with the same proportions.
+    assert!(entity[0] > members[0] && entity[2] > members[2]);
+    assert!((entity[0] / entity[2] - members[0] / members[2]).abs() < 1e-12);
+    assert!((entity[1..].iter().sum::<f64>() - 1.0).abs() < 1e-12);
+    assert!((members.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+    // Unlike when linked, independent speakers favour the nearer member.
+    let spkrs = speakers_2(&speakers, source, 0.0);
+    let independent: Vec<f64> = SpeakerGains::new(&spkrs, 6.0).collect();
+    assert!(independent[0] > independent[1]);
+}
+
+#[cfg(all(feature = "full", feature = "alloc"))]
+#[test]
+fn min_speaker_subset() {
+    let mut candidates = vec![];
+    for &x in &[0.0, 5.0, 10.0, 15.0, 20.0] {
+        for &y in &[0.0, 5.0, 10.0, 15.0, 20.0] {
+            candidates.push(PositionedSpeaker {
+                position: [x, y],
+                weight: 1.0f64,
+            });
+        }
+    }
+    let grid = grid_points_2(&candidates, 8);
+    let floor = 0.15;
+    let subset = min_speaker_subset_2(&candidates, &grid, 6.0, 0.5, floor);
+    assert!(subset.len() > 1 && subset.len() < candidates.len());
+    for &p in &grid {
+        let level = subset
+            .iter()
+            .map(|&i| {
+                let distance = candidates[i].speaker(p, 0.5).distance;
+                rolloff_attenuation(distance, a_coefficient(6.0))
+            })
+            .fold(0.0, f64::max);
+        assert!(level >= floor);
+    }
+    // Lowering the floor extends each speaker's reach, requiring fewer speakers.
+    let fewer = min_speaker_subset_2(&candidates, &grid, 6.0, 0.5, 0.1);
+    assert!(fewer.len() < subset.len());
+}
+
+#[cfg(feature = "capi")]
+#[test]
+fn capi_parity() {
+    use capi::*;
+    use core::ptr;
+    let distances = [1.0f32, 2.0, 4.0, 8.0];
+    let weights = [1.0f32, 0.5, 1.0, 0.25];
+    let spkrs: Vec<_> = distances
+        .iter()
+        .zip(&weights)
+        .map(|(&distance, &weight)| Speaker::new(distance, weight))
+        .collect();
+    let expected: Vec<f32> = SpeakerGains::new(&spkrs, 6.0).collect();
+    let mut out = [0.0f32; 4];
+    let (d, w, o) = (distances.as_ptr(), weights.as_ptr(), out.as_mut_ptr());
+    let status = unsafe { dbap_compute_gains_f32(d, w, 4, 6.0, o) };
+    assert_eq!(status, DbapStatus::DbapOk);
+    assert_eq!(&out[..], &expected[..]);
+    let distances: Vec<f64> = distances.iter().map(|&d| d.into()).collect();
+    let weights: Vec<f64> = weights.iter().map(|&w| w.into()).collect();
+    let spkrs: Vec<Speaker<f64>> = convert_speakers(&spkrs);
+    let expected: Vec<f64> = SpeakerGains::new(&spkrs, 6.0).collect();
+    let mut out = [0.0f64; 4];
+    let (d, w, o) = (distances.as_ptr(), weights.as_ptr(), out.as_mut_ptr());
+    let status = unsafe { dbap_compute_gains_f64(d, w, 4, 6.0, o) };
+    assert_eq!(status, DbapStatus::DbapOk);
+    assert_eq!(&out[..], &expected[..]);
+    // Invalid arguments are rejected without touching the output.
+    let mut out = [-1.0f64; 4];
+    let o = out.as_mut_ptr();
+    let status = unsafe { dbap_compute_gains_f64(ptr::null(), w, 4, 6.0, o) };
+    assert_eq!(status, DbapStatus::DbapNullPointer);
+    let status = unsafe { dbap_compute_gains_f64(d, w, 4, 6.0, ptr::null_mut()) };
+    assert_eq!(status, DbapStatus::DbapNullPointer);
+    let status = unsafe { dbap_compute_gains_f64(d, w, 0, 6.0, o) };
+    assert_eq!(status, DbapStatus::DbapInvalidLength);
+    assert_eq!(out, [-1.0; 4]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn speaker_gate() {
+    let mut gate = SpeakerGate::new(2, 0.1f64, 2, 3, 0.5);
+    assert_eq!(gate.update(&[1.0, 1.0]), &[true, true]);
+    // The idle speaker's envelope falls below the threshold on the 4th update and is gated once it
+    // has remained there for 3 updates.
+    for _ in 0..5 {
+        assert_eq!(gate.update(&[1.0, 0.0]), &[true, true]);
+    }
+    assert_eq!(gate.update(&[1.0, 0.0]), &[true, false]);
+    assert!(gate.envelopes()[1] < 0.1);
+    // A single update above the threshold is shorter than the attack.
+    assert_eq!(gate.update(&[1.0, 0.5]), &[true, false]);
+    assert_eq!(gate.update(&[1.0, 0.0]), &[true, false]);
+    assert_eq!(gate.update(&[1.0, 0.5]), &[true, false]);
+    assert_eq!(gate.update(&[1.0, 0.5]), &[true, true]);
+    assert_eq!(gate.enabled(), &[true, true]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn free_field_gains() {
+    let spkrs = [
+        Speaker::new(1f64, 1.0),
+        Speaker::new(2.5, 0.5),
+        Speaker::new(4.0, 1.0),
+    ];
+    let free_field: Vec<_> = SpeakerGains::free_field(&spkrs).collect();
+    let gains: Vec<_> = SpeakerGains::new(&spkrs, 6.0).collect();
+    assert!(gains_approx_eq(&free_field, &gains, 1e-12));
+    let spkrs: Vec<Speaker<f32>> = spkrs
+        .iter()
+        .map(|s| Speaker::new(s.distance as f32, s.weight as f32))
+        .collect();
+    let free_field: Vec<_> = SpeakerGains::free_field(&spkrs).collect();
+    let gains: Vec<_> = SpeakerGains::new(&spkrs, 6.0).collect();
+    assert!(gains_approx_eq(&free_field, &gains, 1e-6));
+}
+
+#[test]
+fn mahalanobis_distance() {
+    let identity = [[1f64, 0.0], [0.0, 1.0]];
+    let points = [([0.0, 0.0], [3.0, 4.0]), ([-1.5, 2.0], [0.5, -3.0])];
+    for &(source, speaker) in &points {
+        let d = mahalanobis_distance_2(source, speaker, identity, 0.5);
+        assert_eq!(d, blurred_distance_2(source, speaker, 0.5));
+    }
+    // Doubling the weight of the x axis doubles the contribution of the x displacement.
+    let metric = [[2.0, 0.0], [0.0, 1.0]];
+    assert_eq!(
+        mahalanobis_distance_2([0.0, 0.0], [3.0, 4.0], metric, 0.0),
+        34.0
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn incremental_field_removal() {
+    let mut field = IncrementalField::new(6.0);
+    field.push_speaker(Speaker::new(1f64, 1.0));
+    field.push_speaker(Speaker::new(2.5, 0.5));
+    let k = field.k_coefficient();
+    field.push_speaker(Speaker::new(4.0, 2.0));
+    assert_eq!(field.remove_speaker(2), Speaker::new(4.0, 2.0));
+    assert!((field.k_coefficient() - k).abs() < 1e-12);
+    // Removing from the middle preserves the order of the remaining speakers.
+    field.push_speaker(Speaker::new(3.0, 0.0));
+    field.remove_speaker(0);
+    assert_eq!(
+        field.speakers(),
+        &[Speaker::new(2.5, 0.5), Speaker::new(3.0, 0.0)]
+    );
+    let k = k_coefficient(a_coefficient(6.0), field.speakers());
+    assert!((field.k_coefficient() - k).abs() < 1e-12);
+    // A field left with only zero weights is degenerate.
+    field.remove_speaker(0);
+    assert_eq!(field.k_coefficient(), 0.0);
+    assert!(field.gains().is_degenerate());
+    field.remove_speaker(0);
+    assert!(field.speakers().is_empty());
+    assert_eq!(field.k_coefficient(), 0.0);
+}
+
+#[cfg(feature = "full")]
+#[test]
+fn gain_intensity() {
+    assert_eq!(gain_to_intensity(1f64, -60.0), 1.0);
+    assert_eq!(gain_to_intensity(2.0, -60.0), 1.0);
+    assert!(gain_to_intensity(0.001, -60.0).abs() < 1e-12);
+    assert!((gain_to_intensity(0.01, -60.0) - 1.0 / 3.0).abs() < 1e-12);
+    assert_eq!(gain_to_intensity(1e-6, -60.0), 0.0);
+    assert_eq!(gain_to_intensity(0.0, -60.0), 0.0);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn directional_source_gains() {
+    let speaker = |position| PositionedSpeaker {
+        position,
+        weight: 1f64,
+    };
+    let spkrs = [
+        speaker([1.0, 0.0]),
+        speaker([-1.0, 0.0]),
+        speaker([0.0, 1.0]),
+    ];
+    let mut omni = [0.0; 3];
+    let mut cardioid = [0.0; 3];
+    let mut narrow = [0.0; 3];
+    directional_source_gains_2(&spkrs, [0.0, 0.0], [1.0, 0.0], 0.0, 6.0, 0.1, &mut omni);
+    directional_source_gains_2(&spkrs, [0.0, 0.0], [1.0, 0.0], 1.0, 6.0, 0.1, &mut cardioid);
+    directional_source_gains_2(&spkrs, [0.0, 0.0], [1.0, 0.0], 2.0, 6.0, 0.1, &mut narrow);
+    assert!(gains_approx_eq(&omni, &[omni[0]; 3], 1e-12));
+    assert!(cardioid[0] > cardioid[2] && cardioid[2] > cardioid[1]);
+    assert_eq!(cardioid[1], 0.0);
+    assert!(narrow[2] / narrow[0] < cardioid[2] / cardioid[0]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn builder_query() {
+    let spkrs = square_2::<f64>(4.0);
+    let builder = DbapBuilder::new()
+        .rolloff_db(4.5)
+        .blur(0.2)
+        .nearest(3)
+        .normalization(Normalization::ConstantPower);
+    let mut query = builder.build_query_2(&spkrs);
+    for &source in &[[1.0, 1.0], [3.5, 0.5], [2.0, 2.0], [0.0, 4.0]] {
+        assert_eq!(query(source), builder.gains_at_2(&spkrs, source));
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn squared_distance_parity() {
+    let positions = [[0.0f64, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]];
+    let weights = [1.0, 0.5, 0.0, 2.0];
+    let source = [1.0, 3.0];
+    let squared: Vec<_> = positions
+        .iter()
+        .map(|&p| blurred_distance_2(source, p, 0.1))
+        .collect();
+    let spkrs: Vec<_> = squared
+        .iter()
+        .zip(&weights)
+        .map(|(&d2, &weight)| Speaker::new(d2.sqrt(), weight))
+        .collect();
+    let expected: Vec<_> = SpeakerGains::new(&spkrs, 4.5).collect();
+    let mut gains = [0.0; 4];
+    squared_distance_gains(&squared, &weights, 4.5, &mut gains);
+    assert!(gains_approx_eq(&gains, &expected, 1e-12));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn layout_diff() {
+    let old = square_2::<f64>(4.0);
+    let mut new = old;
+    new[2].position = [7.0, 8.0];
+    let source = [1.0, 1.0];
+    let diff = layout_diff_2(&old, &new, source, 6.0, 0.1);
+    assert_eq!(diff.moved(), vec![2]);
+    assert_eq!(diff.displacements, vec![0.0, 0.0, 5.0, 0.0]);
+    assert_eq!(diff.position_deltas[2], [3.0, 4.0]);
+    assert_eq!(diff.weight_deltas, vec![0.0; 4]);
+    // The moved speaker is quieter, while the shift in `k` raises all other gains equally.
+    let old_gains: Vec<_> = SpeakerGains::new(&speakers_2(&old, source, 0.1), 6.0).collect();
+    assert!(diff.gain_deltas[2] < 0.0);
+    let ratio = |i: usize| (old_gains[i] + diff.gain_deltas[i]) / old_gains[i];
+    assert!(ratio(0) > 1.0);
+    assert!((ratio(1) - ratio(0)).abs() < 1e-12);
+    assert!((ratio(3) - ratio(0)).abs() < 1e-12);
+    // Identical layouts produce an empty diff.
+    let diff = layout_diff_2(&old, &old, source, 6.0, 0.1);
+    assert!(diff.moved().is_empty());
+    assert_eq!(diff.gain_deltas, vec![0.0; 4]);
+}
+
+#[test]
+fn per_band_normalization() {
+    // Distinct gains per band, e.g. a broad low band and a narrow high band.
+    let mut bands = [
+        [3.0f64, 4.0, 0.0],
+        [2.0, 2.0, 1.0],
+        [0.0, 0.5, 0.0],
+        [0.0; 3],
+    ];
+    normalize_per_band(&mut bands, Normalization::ConstantPower);
+    let expected = [
+        [0.6, 0.8, 0.0],
+        [2.0 / 3.0, 2.0 / 3.0, 1.0 / 3.0],
+        [0.0, 1.0, 0.0],
+    ];
+    for (band, expected) in bands.iter().zip(&expected) {
+        assert!(gains_approx_eq(band, expected, 1e-12));
+        assert!((total_power(band) - 1.0).abs() < 1e-12);
+    }
+    assert_eq!(bands[3], [0.0; 3]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn attractors() {
+    let source = [0.0f64, 0.0];
+    assert_eq!(apply_attractors_2(source, &[]), source);
+    assert_eq!(apply_attractors_2(source, &[([4.0, 0.0], 0.0)]), source);
+    // A pull of `strength / d` towards the attractor.
+    assert_eq!(apply_attractors_2(source, &[([4.0, 0.0], 8.0)]), [2.0, 0.0]);
+    // Within the capture radius the source lands on the attractor.
+    assert_eq!(
+        apply_attractors_2(source, &[([0.0, 3.0], 16.0)]),
+        [0.0, 3.0]
+    );
+    // Opposing attractors of equal strength cancel.
+    let opposing = [([-4.0, 0.0], 8.0), ([4.0, 0.0], 8.0)];
+    assert_eq!(apply_attractors_2(source, &opposing), source);
+    // The nudged position drives the regular computation.
+    let spkrs = [
+        PositionedSpeaker {
+            position: [-4.0, 0.0],
+            weight: 1.0,
+        },
+        PositionedSpeaker {
+            position: [4.0, 0.0],
+            weight: 1.0,
+        },
+    ];
+    let nudged = apply_attractors_2(source, &[([4.0, 0.0], 8.0)]);
+    let gains: Vec<_> = SpeakerGains::new(&speakers_2(&spkrs, nudged, 0.1), 6.0).collect();
+    assert!(gains[1] > gains[0]);
+}
+
+#[cfg(all(feature = "full", feature = "alloc"))]
+#[test]
+fn apparent_source_width() {
+    assert_eq!(apparent_width(&[0.0f64, 1.0, 0.0, 0.0]), 0.0);
+    assert!((apparent_width(&[0.5f64; 4]) - 1.0).abs() < 1e-12);
+    assert_eq!(apparent_width(&[0.0f64; 4]), 0.0);
+    assert_eq!(apparent_width(&[1.0f64]), 0.0);
+    // Power spread evenly across two of four speakers.
+    let half = 0.5f64.sqrt();
+    assert!((apparent_width(&[half, half, 0.0, 0.0]) - 1.0 / 3.0).abs() < 1e-12);
+    // A blurrier source is wider.
+    let spkrs = [
+        PositionedSpeaker {
+            position: [0.0, 0.0],
+            weight: 1.0,
+        },
+        PositionedSpeaker {
+            position: [4.0, 0.0],
+            weight: 1.0,
+        },
+    ];
+    let width = |blur| {
+        let gains: Vec<_> = SpeakerGains::new(&speakers_2(&spkrs, [0.5, 0.0], blur), 6.0).collect();
+        apparent_width(&gains)
+    };
+    assert!(width(2.0) > width(0.1));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn normalized_to_db_power() {
+    let spkrs = [
+        Speaker::new(1f64, 1.0),
+        Speaker::new(2.5, 0.5),
+        Speaker::new(4.0, 1.0),
+    ];
+    for &target_db in &[0.0, -6.0, -20.0, 3.0] {
+        let gains = SpeakerGains::new(&spkrs, 6.0).normalized_to_db_power(target_db);
+        let power_db = 10.0 * total_power(&gains).log10();
+        assert!((power_db - target_db).abs() < 1e-9);
+    }
+    // The calibration trims and the master are applied on top of the target power.
+    let calibration_db = [-6.0, 0.0, 20.0];
+    let reference = SpeakerGains::new(&spkrs, 6.0).normalized_to_db_power(-6.0);
+    let trimmed = SpeakerGains::new(&spkrs, 6.0)
+        .with_calibration(&calibration_db)
+        .with_master(0.5, false)
+        .normalized_to_db_power(-6.0);
+    for ((t, r), &db) in trimmed.iter().zip(&reference).zip(&calibration_db) {
+        assert!((t - r * 10f64.powf(db / 20.0) * 0.5).abs() < 1e-12);
+    }
+    let silent = [Speaker::new(1.0, 0.0), Speaker::new(2.0, 0.0)];
+    let gains = SpeakerGains::new(&silent, 6.0).normalized_to_db_power(-6.0);
+    assert_eq!(gains, vec![0.0; 2]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn audience_weights() {
+    let spkrs = square_2::<f64>(10.0);
+    // A triangular audience in the corner nearest the first speaker.
+    let audience = [[0.5, 0.5], [4.0, 0.5], [0.5, 4.0]];
+    let weights = audience_weights_2(&spkrs, &audience, 6.0, 0.5, 16);
+    assert_eq!(weights[0], 1.0);
+    assert!(weights[1] < weights[0] && weights[3] < weights[0]);
+    assert!((weights[1] - weights[3]).abs() < 1e-12);
+    assert!(weights[2] < weights[1]);
+    assert!(point_in_polygon_2([1.0, 1.0], &audience));
+    assert!(!point_in_polygon_2([3.0, 3.0], &audience));
+    // A degenerate polygon leaves the weights uniform.
+    assert_eq!(
+        audience_weights_2(&spkrs, &audience[..2], 6.0, 0.5, 16),
+        vec![1.0; 4]
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn zip_speakers() {
+    let spkrs = [
+        Speaker::new(1f64, 1.0),
+        Speaker::new(2.5, 0.5),
+        Speaker::new(4.0, 1.0),
+    ];
+    let gains: Vec<_> = SpeakerGains::new(&spkrs, 6.0).collect();
+    let mut iter = SpeakerGains::new(&spkrs, 6.0);
+    iter.next();
+    let pairs: Vec<_> = iter.zip_speakers().collect();
+    assert_eq!(pairs.len(), 2);
+    for (i, &(s, gain)) in pairs.iter().enumerate() {
+        assert!(core::ptr::eq(s, &spkrs[i + 1]));
+        assert_eq!(gain, gains[i + 1]);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn spl_calibration() {
+    let measured = [90.0f64, 96.0, 90.0, 87.0];
+    let weights = calibrate_weights_from_spl(&measured, 90.0);
+    assert_eq!(weights[0], 1.0);
+    assert_eq!(weights[2], 1.0);
+    assert!((weights[1] - 0.5).abs() < 0.002);
+    assert!(weights[3] > 1.0);
+    // Each weighted speaker now produces the same level at the microphone.
+    for (&w, &spl) in weights.iter().zip(&measured) {
+        assert!((20.0 * w.log10() + spl - 90.0).abs() < 1e-9);
+    }
+}
+
+#[cfg(all(feature = "full", feature = "alloc"))]
+#[test]
+fn speed_adaptive_gains() {
+    let speaker = |position, weight| PositionedSpeaker { position, weight };
+    let spkrs = [
+        speaker([0.0f64, 0.0], 1.0),
+        speaker([4.0, 0.0], 0.5),
+        speaker([2.0, 3.0], 1.0),
+    ];
+    let source = [1.0, 1.0];
+    let expected = |rolloff_db| {
+        let mut gains = [0.0; 3];
+        let spkrs = speakers_2(&spkrs, source, 0.1);
+        modelled_gains(&spkrs, &RolloffModel::Constant(rolloff_db), &mut gains);
+        gains
+    };
+    let gains_at = |speed| {
+        let mut gains = [0.0; 3];
+        gains_speed_adaptive_2(&spkrs, source, speed, 3.0, 9.0, 2.0, 0.1, &mut gains);
+        gains
+    };
+    let (slow, fast) = (gains_at(0.0), gains_at(2.0));
+    assert_eq!(slow, expected(3.0));
+    assert_eq!(fast, expected(9.0));
+    assert_eq!(gains_at(5.0), expected(9.0));
+    // The fast source is drawn towards the nearest speaker.
+    assert!(fast[0] > slow[0]);
+    assert!(fast[1] < slow[1] && fast[2] < slow[2]);
+}
+
+#[test]
+fn speed_adaptive_rolloff_interpolation() {
+    assert_eq!(speed_adaptive_rolloff(0.0f64, 3.0, 9.0, 2.0), 3.0);
+    assert_eq!(speed_adaptive_rolloff(1.0, 3.0, 9.0, 2.0), 6.0);
+    assert_eq!(speed_adaptive_rolloff(1.5, 3.0, 9.0, 2.0), 7.5);
+    assert_eq!(speed_adaptive_rolloff(2.0, 3.0, 9.0, 2.0), 9.0);
+    assert_eq!(speed_adaptive_rolloff(5.0, 3.0, 9.0, 2.0), 9.0);
+    assert_eq!(speed_adaptive_rolloff(-1.0, 3.0, 9.0, 2.0), 3.0);
+    assert_eq!(speed_adaptive_rolloff(0.0, 3.0, 9.0, 0.0), 9.0);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn gain_at_position() {
+    let speaker = |position, weight| PositionedSpeaker { position, weight };
+    let spkrs = [
+        speaker([0.0f64, 0.0], 1.0),
+        speaker([4.0, 0.0], 0.5),
+        speaker([2.0, 3.0], 1.0),
+    ];
+    let source = [1.0, 1.0];
+    let gains: Vec<_> = SpeakerGains::new(&speakers_2(&spkrs, source, 0.1), 6.0).collect();
+    for (s, &gain) in spkrs.iter().zip(&gains) {
+        assert_eq!(
+            gain_at_position_2(&spkrs, source, s.position, 0.0, 6.0, 0.1),
+            Some(gain)
+        );
+    }
+    let near = gain_at_position_2(&spkrs, source, [4.01, 0.0], 0.05, 6.0, 0.1);
+    assert_eq!(near, Some(gains[1]));
+    assert_eq!(
+        gain_at_position_2(&spkrs, source, [2.0, 1.0], 0.05, 6.0, 0.1),
+        None
+    );
+    assert_eq!(
+        gain_at_position_2(&[], source, [0.0, 0.0], 0.05, 6.0, 0.1),
+        None
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn precomputed_a_coefficient() {
+    let spkrs = [
+        Speaker::new(1f64, 1.0),
+        Speaker::new(2.5, 0.5),
+        Speaker::new(4.0, 1.0),
+    ];
+    let a = a_coefficient(6.0);
+    for subset in &[&spkrs[..], &spkrs[..2], &spkrs[1..]] {
+        let gains: Vec<_> = SpeakerGains::with_a_coefficient(subset, a).collect();
+        let expected: Vec<_> = SpeakerGains::new(subset, 6.0).collect();
+        assert_eq!(gains, expected);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn stabilize_near_equal_gains() {
+    let mut gains = [0.3301f64, 0.1, 0.33, 0.3299, 0.5];
+    stabilize_gains(&mut gains, 0.001);
+    assert_eq!(gains[0], gains[2]);
+    assert_eq!(gains[2], gains[3]);
+    assert!((gains[0] - 0.33).abs() < 1e-12);
+    assert_eq!((gains[1], gains[4]), (0.1, 0.5));
+    let mut unchanged = [0.3301f64, 0.33, 0.3299];
+    stabilize_gains(&mut unchanged, 0.0);
+    assert_eq!(unchanged, [0.3301, 0.33, 0.3299]);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_gains_batch_matches_sequential() {
+    let speaker = |position, weight| PositionedSpeaker { position, weight };
+    let spkrs = [
+        speaker([0.0f64, 0.0], 1.0),
+        speaker([4.0, 0.0], 0.5),
+        speaker([4.0, 4.0], 1.0),
+        speaker([0.0, 4.0], 2.0),
+    ];
+    let sources: Vec<[f64; 2]> = (0..64)
+        .map(|i| [f64::from(i % 8) * 0.5, f64::from(i / 8) * 0.5])
+        .collect();
+    let expected: Vec<_> = gains_over_path_2(&spkrs, sources.iter().cloned(), 6.0, 0.1).collect();
+    assert_eq!(par_gains_batch(&spkrs, &sources, 6.0, 0.1), expected);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn ranked_gains() {
+    let spkrs = [
+        Speaker::new(3f64, 1.0),
+        Speaker::new(1.0, 1.0),
+        Speaker::new(2.0, 1.0),
+        Speaker::new(1.0, 1.0),
+        Speaker::new(2.0, 0.0),
+    ];
+    let gains: Vec<_> = SpeakerGains::new(&spkrs, 6.0).collect();
+    let ranked = SpeakerGains::new(&spkrs, 6.0).ranked();
+    let indices: Vec<_> = ranked.iter().map(|&(i, _)| i).collect();
+    assert_eq!(indices, vec![1, 3, 2, 0, 4]);
+    for &(i, g) in &ranked {
+        assert_eq!(g, gains[i]);
+    }
+    // Indices remain those of the original speakers after partial iteration.
+    let mut partial = SpeakerGains::new(&spkrs, 6.0);
+    partial.next();
+    partial.next();
+    let indices: Vec<_> = partial.ranked().iter().map(|&(i, _)| i).collect();
+    assert_eq!(indices, vec![3, 2, 4]);
+}
+
+#[test]
+fn prune_inaudible_gains() {
+    let mut gains = [0.8f64, 0.001, 0.5, 0.0005, 0.3];
+    let power = total_power(&gains);
+    prune_inaudible(&mut gains, 0.01);
+    assert_eq!((gains[1], gains[3]), (0.0, 0.0));
+    assert!((total_power(&gains) - power).abs() < 1e-12);
+    assert!((gains[0] / gains[2] - 0.8 / 0.5).abs() < 1e-12);
+    // When every gain is inaudible the gains are left unchanged.
+    let mut quiet = [0.001f64, 0.002];
+    prune_inaudible(&mut quiet, 0.01);
+    assert_eq!(quiet, [0.001, 0.002]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn gain_reductions() {
+    // Four equidistant speakers of equal weight each receive a gain of `0.25`.
+    let equal = [Speaker::new(2f64, 1.0); 4];
+    assert!((SpeakerGains::new(&equal, 6.0).peak() - 0.25).abs() < 1e-12);
+    assert!((SpeakerGains::new(&equal, 6.0).gain_sum() - 1.0).abs() < 1e-12);
+    assert!((SpeakerGains::new(&equal, 6.0).rms() - 0.25).abs() < 1e-12);
+    let spkrs = [
+        Speaker::new(1.0, 1.0),
+        Speaker::new(2.5, 0.5),
+        Speaker::new(4.0, 1.0),
+    ];
+    let gains: Vec<_> = SpeakerGains::new(&spkrs, 6.0).collect();
+    let count = SpeakerGains::new(&spkrs, 6.0).fold_gains(0, |n, g| n + (g > 0.2) as usize);
+    assert_eq!(count, gains.iter().filter(|&&g| g > 0.2).count());
+    assert_eq!(SpeakerGains::new(&spkrs, 6.0).peak(), gains[0]);
+    let sum: f64 = gains.iter().sum();
+    assert!((SpeakerGains::new(&spkrs, 6.0).gain_sum() - sum).abs() < 1e-12);
+    assert_eq!(SpeakerGains::new(&spkrs, 6.0).sum::<f64>(), sum);
+    let rms = (total_power(&gains) / 3.0).sqrt();
+    assert!((SpeakerGains::new(&spkrs, 6.0).rms() - rms).abs() < 1e-12);
+    let mut exhausted = SpeakerGains::new(&spkrs, 6.0);
+    exhausted.by_ref().for_each(drop);
+    assert_eq!(exhausted.rms(), 0.0);
+}